@@ -0,0 +1,928 @@
+//! Local mock of the Deribit JSON-RPC WebSocket API.
+//!
+//! Speaks just enough of the protocol for the latency tester to run its
+//! order scenarios offline: authentication, heartbeats, subscriptions with
+//! book / trades / ticker / quote pushes, instrument / ticker lookups and order entry
+//! (with cancel-on-disconnect).
+//! Every response and push is delayed by a configurable network latency plus jitter;
+//! responses carry synthetic `usIn` / `usOut` / `usDiff` engine timestamps.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use clap::Parser;
+use futures::{SinkExt, StreamExt};
+use rand::Rng;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, Mutex};
+use tokio::time::{sleep, sleep_until, Instant};
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+
+/// Tail of every simulated delay that is spun instead of slept (tokio timers have 1 ms
+/// resolution).
+const SPIN_WINDOW: Duration = Duration::from_millis(1);
+
+/// Command-line options of the mock server.
+#[derive(Debug, Clone, Parser)]
+#[command(
+    name = "deribit-mock-server",
+    about = "Local mock Deribit WebSocket server"
+)]
+struct Args {
+    /// Address to listen on.
+    #[arg(long, default_value = "127.0.0.1:8765")]
+    listen: SocketAddr,
+
+    /// One-way network latency added to every request and response, in microseconds.
+    #[arg(long, default_value_t = 500)]
+    latency_us: u64,
+
+    /// Maximum random jitter added on top of each one-way latency, in microseconds.
+    #[arg(long, default_value_t = 200)]
+    jitter_us: u64,
+
+    /// Simulated matching engine processing time (`usDiff`), in microseconds.
+    #[arg(long, default_value_t = 50)]
+    engine_us: u64,
+
+    /// Maximum random jitter added to the engine processing time, in microseconds.
+    #[arg(long, default_value_t = 20)]
+    engine_jitter_us: u64,
+
+    /// Interval between pushes of each `raw` channel, in milliseconds (`100ms` channels
+    /// push every 100 ms).
+    #[arg(long, default_value_t = 100)]
+    book_interval_ms: u64,
+
+    /// Mark price reported by `public/ticker` and used as book mid price.
+    #[arg(long, default_value_t = 100_000.0)]
+    base_price: f64,
+
+    /// Tick size reported by `public/get_instrument`.
+    #[arg(long, default_value_t = 0.5)]
+    tick_size: f64,
+
+    /// Forcibly close every connection after this many milliseconds (0 = never).
+    /// Useful for exercising the client's reconnect logic.
+    #[arg(long, default_value_t = 0)]
+    disconnect_every_ms: u64,
+
+    /// Offset of the mock's clock (`usIn`, `usOut`, `public/get_time`, book timestamps)
+    /// from the host clock, in microseconds. Useful for checking clock offset estimation.
+    #[arg(long, default_value_t = 0, allow_negative_numbers = true)]
+    clock_offset_us: i64,
+
+    /// Fraction of `private/*` responses (0.0–1.0) silently dropped, to exercise client timeouts.
+    #[arg(long, default_value_t = 0.0)]
+    drop_rate: f64,
+
+    /// Fraction of `book.*` change notifications (0.0–1.0) silently skipped, leaving a
+    /// `change_id` gap, to exercise the client's sequence gap detection.
+    #[arg(long, default_value_t = 0.0)]
+    book_gap_rate: f64,
+}
+
+/// JSON-RPC error returned to the client.
+struct RpcError {
+    code: i64,
+    message: &'static str,
+}
+
+impl RpcError {
+    const METHOD_NOT_FOUND: RpcError = RpcError {
+        code: -32601,
+        message: "Method not found",
+    };
+    const INVALID_PARAMS: RpcError = RpcError {
+        code: -32602,
+        message: "Invalid params",
+    };
+    const UNAUTHORIZED: RpcError = RpcError {
+        code: 13009,
+        message: "unauthorized",
+    };
+    const NOT_OPEN_ORDER: RpcError = RpcError {
+        code: 11044,
+        message: "not_open_order",
+    };
+    const ORDER_NOT_FOUND: RpcError = RpcError {
+        code: 10004,
+        message: "order_not_found",
+    };
+}
+
+/// Resting order kept by the mock matching engine.
+#[derive(Debug, Clone)]
+struct Order {
+    order_id: String,
+    instrument_name: String,
+    direction: &'static str,
+    amount: f64,
+    price: f64,
+    order_state: &'static str,
+    post_only: bool,
+    label: String,
+    creation_timestamp: i64,
+    last_update_timestamp: i64,
+    /// Connection the order was placed on (for cancel-on-disconnect).
+    connection_id: u64,
+}
+
+impl Order {
+    fn to_json(&self) -> Value {
+        json!({
+            "order_id": self.order_id,
+            "instrument_name": self.instrument_name,
+            "direction": self.direction,
+            "amount": self.amount,
+            "filled_amount": 0.0,
+            "price": self.price,
+            "order_state": self.order_state,
+            "order_type": "limit",
+            "time_in_force": "good_til_cancelled",
+            "post_only": self.post_only,
+            "label": self.label,
+            "creation_timestamp": self.creation_timestamp,
+            "last_update_timestamp": self.last_update_timestamp,
+        })
+    }
+}
+
+/// State shared by all connections (the "exchange").
+#[derive(Default)]
+struct Exchange {
+    orders: HashMap<String, Order>,
+    next_order_id: u64,
+    next_connection_id: u64,
+    /// `private/enable_cancel_on_disconnect` with `scope = account` was called.
+    cancel_on_disconnect: bool,
+}
+
+impl Exchange {
+    /// Cancel (remove) every open order matching `pred`; returns how many were cancelled.
+    fn cancel_where(&mut self, pred: impl Fn(&Order) -> bool) -> usize {
+        let before = self.orders.len();
+        self.orders.retain(|_, o| !pred(o));
+        before - self.orders.len()
+    }
+}
+
+/// Per-connection state.
+#[derive(Default)]
+struct Session {
+    connection_id: u64,
+    authenticated: bool,
+    /// `private/enable_cancel_on_disconnect` with `scope = connection` was called on this
+    /// connection.
+    cancel_on_disconnect: bool,
+    /// Subscribed channels; clearing a channel's flag stops its feed.
+    subscriptions: HashMap<String, Arc<AtomicBool>>,
+    /// Bumped by every `set_heartbeat` / `disable_heartbeat` to stop older heartbeat tasks.
+    heartbeat_generation: u64,
+    /// A `test_request` was sent and not yet answered with `public/test`.
+    awaiting_test: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Arc::new(Args::parse());
+    let exchange = Arc::new(Mutex::new(Exchange::default()));
+
+    let listener = TcpListener::bind(args.listen).await?;
+    println!(
+        "[{}] Mock Deribit server listening on ws://{} (latency={}µs, jitter={}µs, engine={}µs)",
+        Utc::now().to_rfc3339(),
+        args.listen,
+        args.latency_us,
+        args.jitter_us,
+        args.engine_us
+    );
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let args = Arc::clone(&args);
+        let exchange = Arc::clone(&exchange);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, args, exchange).await {
+                eprintln!(
+                    "[{}] Connection {peer} failed: {e}",
+                    Utc::now().to_rfc3339()
+                );
+            }
+        });
+    }
+}
+
+async fn handle_connection(
+    stream: TcpStream,
+    args: Arc<Args>,
+    exchange: Arc<Mutex<Exchange>>,
+) -> Result<()> {
+    stream.set_nodelay(true)?;
+    let ws_stream = accept_async(stream).await?;
+    let (mut ws_tx, mut ws_rx) = ws_stream.split();
+
+    // All outgoing frames go through a single writer task so that delayed
+    // responses and book pushes can be produced from independent tasks.
+    // Sending `Message::Close` closes the connection.
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<Message>();
+    tokio::spawn(async move {
+        while let Some(msg) = out_rx.recv().await {
+            let is_close = matches!(msg, Message::Close(_));
+            if ws_tx.send(msg).await.is_err() {
+                break;
+            }
+            if is_close {
+                let _ = ws_tx.close().await;
+                break;
+            }
+        }
+    });
+
+    let connection_id = {
+        let mut guard = exchange.lock().await;
+        guard.next_connection_id += 1;
+        guard.next_connection_id
+    };
+    let session = Arc::new(Mutex::new(Session {
+        connection_id,
+        ..Session::default()
+    }));
+
+    let disconnect_at = (args.disconnect_every_ms > 0)
+        .then(|| Instant::now() + Duration::from_millis(args.disconnect_every_ms));
+
+    let mut outcome = Ok(());
+    loop {
+        let next = match disconnect_at {
+            Some(deadline) => tokio::select! {
+                msg = ws_rx.next() => msg,
+                _ = sleep_until(deadline) => {
+                    println!(
+                        "[{}] Dropping connection (--disconnect-every-ms)",
+                        Utc::now().to_rfc3339()
+                    );
+                    let _ = out_tx.send(Message::Close(None));
+                    break;
+                }
+            },
+            None => ws_rx.next().await,
+        };
+        let Some(msg) = next else {
+            break;
+        };
+        let txt = match msg {
+            Ok(Message::Text(txt)) => txt,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                outcome = Err(e.into());
+                break;
+            }
+        };
+        let Ok(req) = serde_json::from_str::<Value>(&txt) else {
+            continue;
+        };
+
+        let args = Arc::clone(&args);
+        let exchange = Arc::clone(&exchange);
+        let session = Arc::clone(&session);
+        let out_tx = out_tx.clone();
+        tokio::spawn(async move {
+            // Inbound network leg
+            precise_sleep(one_way_delay(&args)).await;
+
+            let us_in = server_now_us(&args);
+            let id = req.get("id").cloned().unwrap_or(Value::Null);
+            let method = req.get("method").and_then(|m| m.as_str()).unwrap_or("");
+            let params = req.get("params").cloned().unwrap_or_else(|| json!({}));
+
+            let outcome = dispatch(method, &params, &args, &exchange, &session, &out_tx).await;
+
+            // Matching engine processing time
+            precise_sleep(jittered(args.engine_us, args.engine_jitter_us)).await;
+            let us_out = server_now_us(&args);
+
+            if let Ok(result) = &outcome {
+                notify_order_change(method, result, &args, &session, &out_tx).await;
+            }
+
+            let mut resp = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "usIn": us_in,
+                "usOut": us_out,
+                "usDiff": us_out - us_in,
+                "testnet": true,
+            });
+            match outcome {
+                Ok(result) => resp["result"] = result,
+                Err(err) => {
+                    resp["error"] = json!({ "code": err.code, "message": err.message });
+                }
+            }
+
+            if method.starts_with("private/")
+                && args.drop_rate > 0.0
+                && rand::thread_rng().gen_bool(args.drop_rate.min(1.0))
+            {
+                return;
+            }
+
+            // Outbound network leg
+            precise_sleep(one_way_delay(&args)).await;
+            let _ = out_tx.send(Message::Text(resp.to_string()));
+        });
+    }
+
+    // However the connection ended, its orders go with it if cancel-on-disconnect is on
+    let connection_scope = session.lock().await.cancel_on_disconnect;
+    let mut guard = exchange.lock().await;
+    if connection_scope || guard.cancel_on_disconnect {
+        let cancelled = guard.cancel_where(|o| o.connection_id == connection_id);
+        println!(
+            "[{}] Cancelled {} order(s) on disconnect",
+            Utc::now().to_rfc3339(),
+            cancelled
+        );
+    }
+
+    outcome
+}
+
+async fn dispatch(
+    method: &str,
+    params: &Value,
+    args: &Arc<Args>,
+    exchange: &Arc<Mutex<Exchange>>,
+    session: &Arc<Mutex<Session>>,
+    out_tx: &mpsc::UnboundedSender<Message>,
+) -> Result<Value, RpcError> {
+    if method.starts_with("private/") && !session.lock().await.authenticated {
+        return Err(RpcError::UNAUTHORIZED);
+    }
+
+    match method {
+        "public/auth" => {
+            session.lock().await.authenticated = true;
+            Ok(json!({
+                "access_token": "mock-access-token",
+                "refresh_token": "mock-refresh-token",
+                "expires_in": 31_536_000,
+                "scope": "connection mainaccount trade:read_write",
+                "token_type": "bearer",
+            }))
+        }
+        "public/set_heartbeat" => {
+            let interval = f64_param(params, "interval")?;
+            if interval <= 0.0 {
+                return Err(RpcError::INVALID_PARAMS);
+            }
+            let mut guard = session.lock().await;
+            guard.heartbeat_generation += 1;
+            guard.awaiting_test = false;
+            spawn_heartbeat(
+                Duration::from_secs_f64(interval),
+                guard.heartbeat_generation,
+                Arc::clone(session),
+                out_tx.clone(),
+            );
+            Ok(json!("ok"))
+        }
+        "public/disable_heartbeat" => {
+            let mut guard = session.lock().await;
+            guard.heartbeat_generation += 1;
+            guard.awaiting_test = false;
+            Ok(json!("ok"))
+        }
+        "public/test" => {
+            session.lock().await.awaiting_test = false;
+            Ok(json!({ "version": "mock" }))
+        }
+        "public/subscribe" | "private/subscribe" => {
+            let channels: Vec<String> = params
+                .get("channels")
+                .and_then(|c| c.as_array())
+                .ok_or(RpcError::INVALID_PARAMS)?
+                .iter()
+                .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                .collect();
+
+            let mut guard = session.lock().await;
+            for channel in &channels {
+                if guard.subscriptions.contains_key(channel) {
+                    continue;
+                }
+                let active = Arc::new(AtomicBool::new(true));
+                guard
+                    .subscriptions
+                    .insert(channel.clone(), Arc::clone(&active));
+                spawn_channel_feed(channel, active, Arc::clone(args), out_tx.clone());
+            }
+            Ok(json!(channels))
+        }
+        "public/unsubscribe" | "private/unsubscribe" => {
+            let channels: Vec<String> = params
+                .get("channels")
+                .and_then(|c| c.as_array())
+                .ok_or(RpcError::INVALID_PARAMS)?
+                .iter()
+                .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                .collect();
+
+            let mut guard = session.lock().await;
+            let removed: Vec<&String> = channels
+                .iter()
+                .filter(|channel| match guard.subscriptions.remove(*channel) {
+                    Some(active) => {
+                        active.store(false, Ordering::Relaxed);
+                        true
+                    }
+                    None => false,
+                })
+                .collect();
+            Ok(json!(removed))
+        }
+        "public/get_instrument" => {
+            let instrument = instrument_param(params)?;
+            Ok(json!({
+                "instrument_name": instrument,
+                "kind": "future",
+                "tick_size": args.tick_size,
+                "min_trade_amount": 0.0001,
+                "contract_size": 0.0001,
+                "is_active": true,
+            }))
+        }
+        "public/get_time" => Ok(json!(server_now_us(args) / 1000)),
+        "public/ticker" => {
+            let instrument = instrument_param(params)?;
+            Ok(json!({
+                "instrument_name": instrument,
+                "mark_price": args.base_price,
+                "last_price": args.base_price,
+                "best_bid_price": args.base_price - args.tick_size,
+                "best_ask_price": args.base_price + args.tick_size,
+                "timestamp": server_now_us(args) / 1000,
+            }))
+        }
+        "private/enable_cancel_on_disconnect" => {
+            match params.get("scope").and_then(|v| v.as_str()) {
+                None | Some("connection") => session.lock().await.cancel_on_disconnect = true,
+                Some("account") => exchange.lock().await.cancel_on_disconnect = true,
+                Some(_) => return Err(RpcError::INVALID_PARAMS),
+            }
+            Ok(json!("ok"))
+        }
+        "private/buy" | "private/sell" => {
+            let direction = if method == "private/buy" {
+                "buy"
+            } else {
+                "sell"
+            };
+            let instrument = instrument_param(params)?;
+            let amount = f64_param(params, "amount")?;
+            let price = f64_param(params, "price")?;
+            let now_ms = server_now_us(args) / 1000;
+            let connection_id = session.lock().await.connection_id;
+
+            let mut guard = exchange.lock().await;
+            guard.next_order_id += 1;
+            let order = Order {
+                order_id: format!("MOCK-{}", guard.next_order_id),
+                instrument_name: instrument.to_string(),
+                direction,
+                amount,
+                price,
+                order_state: "open",
+                post_only: params
+                    .get("post_only")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false),
+                label: params
+                    .get("label")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+                creation_timestamp: now_ms,
+                last_update_timestamp: now_ms,
+                connection_id,
+            };
+            let order_json = order.to_json();
+            guard.orders.insert(order.order_id.clone(), order);
+            Ok(json!({ "order": order_json, "trades": [] }))
+        }
+        "private/edit" => {
+            let order_id = str_param(params, "order_id")?;
+            let amount = f64_param(params, "amount")?;
+            let price = f64_param(params, "price")?;
+
+            let mut guard = exchange.lock().await;
+            let order = guard
+                .orders
+                .get_mut(order_id)
+                .filter(|o| o.order_state == "open")
+                .ok_or(RpcError::NOT_OPEN_ORDER)?;
+            order.amount = amount;
+            order.price = price;
+            order.last_update_timestamp = server_now_us(args) / 1000;
+            Ok(json!({ "order": order.to_json(), "trades": [] }))
+        }
+        "private/cancel" => {
+            let order_id = str_param(params, "order_id")?;
+
+            let mut guard = exchange.lock().await;
+            let mut order = guard
+                .orders
+                .remove(order_id)
+                .filter(|o| o.order_state == "open")
+                .ok_or(RpcError::NOT_OPEN_ORDER)?;
+            order.order_state = "cancelled";
+            order.last_update_timestamp = server_now_us(args) / 1000;
+            Ok(order.to_json())
+        }
+        "private/cancel_all" => {
+            let mut guard = exchange.lock().await;
+            Ok(json!(guard.cancel_where(|_| true)))
+        }
+        "private/cancel_all_by_instrument" => {
+            let instrument = instrument_param(params)?;
+            let mut guard = exchange.lock().await;
+            let cancelled = guard.cancel_where(|o| o.instrument_name == instrument);
+            Ok(json!(cancelled))
+        }
+        "private/cancel_all_by_currency" => {
+            let currency = str_param(params, "currency")?;
+            let mut guard = exchange.lock().await;
+            let cancelled =
+                guard.cancel_where(|o| settlement_currency(&o.instrument_name) == currency);
+            Ok(json!(cancelled))
+        }
+        "private/cancel_by_label" => {
+            let label = str_param(params, "label")?;
+            let mut guard = exchange.lock().await;
+            Ok(json!(guard.cancel_where(|o| o.label == label)))
+        }
+        "private/get_order_state" => {
+            let order_id = str_param(params, "order_id")?;
+            let guard = exchange.lock().await;
+            let order = guard
+                .orders
+                .get(order_id)
+                .ok_or(RpcError::ORDER_NOT_FOUND)?;
+            Ok(order.to_json())
+        }
+        "private/get_open_orders_by_instrument" => {
+            let instrument = instrument_param(params)?;
+            let guard = exchange.lock().await;
+            let orders: Vec<Value> = guard
+                .orders
+                .values()
+                .filter(|o| o.instrument_name == instrument)
+                .map(Order::to_json)
+                .collect();
+            Ok(json!(orders))
+        }
+        _ => Err(RpcError::METHOD_NOT_FOUND),
+    }
+}
+
+/// Start pushing notifications for a supported channel (`book.<instrument>.<interval>`,
+/// `trades.<instrument>.<interval>`, `ticker.<instrument>.<interval>`, `quote.<instrument>`);
+/// other channels are accepted but stay silent.
+fn spawn_channel_feed(
+    channel: &str,
+    active: Arc<AtomicBool>,
+    args: Arc<Args>,
+    out_tx: mpsc::UnboundedSender<Message>,
+) {
+    let parts: Vec<&str> = channel.split('.').collect();
+    let (kind, instrument, interval) = match parts.as_slice() {
+        ["book", instrument, interval] => ("book", *instrument, *interval),
+        ["trades", instrument, interval] => ("trades", *instrument, *interval),
+        ["ticker", instrument, interval] => ("ticker", *instrument, *interval),
+        ["quote", instrument] => ("quote", *instrument, "raw"),
+        _ => return,
+    };
+    let interval = match interval {
+        "raw" => Duration::from_millis(args.book_interval_ms),
+        "100ms" => Duration::from_millis(100),
+        _ => return,
+    };
+
+    let instrument = instrument.to_string();
+    let feed = Feed {
+        channel: channel.to_string(),
+        active,
+        args,
+        out_tx,
+    };
+    if kind == "book" {
+        spawn_book_feed(feed, instrument, interval);
+    } else {
+        spawn_market_feed(feed, kind, instrument, interval);
+    }
+}
+
+/// One subscribed channel of a connection.
+struct Feed {
+    channel: String,
+    /// Cleared by `public/unsubscribe`.
+    active: Arc<AtomicBool>,
+    args: Arc<Args>,
+    out_tx: mpsc::UnboundedSender<Message>,
+}
+
+impl Feed {
+    /// Push `data` after the simulated network delay; `false` once the channel was
+    /// unsubscribed or the connection is gone.
+    ///
+    /// The exchange timestamp is taken before the delay, so the receiver sees the feed latency.
+    async fn push(&self, data: Value) -> bool {
+        precise_sleep(one_way_delay(&self.args)).await;
+        self.active.load(Ordering::Relaxed)
+            && self
+                .out_tx
+                .send(subscription_message(&self.channel, data))
+                .is_ok()
+    }
+}
+
+/// Periodically push `book.<instrument>.<interval>` changes until the connection goes away.
+fn spawn_book_feed(feed: Feed, instrument: String, interval: Duration) {
+    tokio::spawn(async move {
+        let args = Arc::clone(&feed.args);
+        let mut change_id: i64 = 1;
+        let mut mid = args.base_price;
+
+        let snapshot = json!({
+            "type": "snapshot",
+            "timestamp": server_now_us(&args) / 1000,
+            "instrument_name": instrument,
+            "change_id": change_id,
+            "bids": [["new", mid - args.tick_size, 10.0]],
+            "asks": [["new", mid + args.tick_size, 10.0]],
+        });
+        if !feed.push(snapshot).await {
+            return;
+        }
+
+        loop {
+            sleep(interval).await;
+
+            let step = rand::thread_rng().gen_range(-1..=1) as f64;
+            let prev_mid = mid;
+            mid += step * args.tick_size;
+            let amount = rand::thread_rng().gen_range(1..=20) as f64;
+
+            // One level per side: move it (delete + new) or change its amount
+            let (bids, asks) = if mid == prev_mid {
+                (
+                    json!([["change", mid - args.tick_size, amount]]),
+                    json!([["change", mid + args.tick_size, amount]]),
+                )
+            } else {
+                (
+                    json!([
+                        ["delete", prev_mid - args.tick_size, 0.0],
+                        ["new", mid - args.tick_size, amount]
+                    ]),
+                    json!([
+                        ["delete", prev_mid + args.tick_size, 0.0],
+                        ["new", mid + args.tick_size, amount]
+                    ]),
+                )
+            };
+
+            let prev_change_id = change_id;
+            change_id += 1;
+            let data = json!({
+                "type": "change",
+                "timestamp": server_now_us(&args) / 1000,
+                "instrument_name": instrument,
+                "prev_change_id": prev_change_id,
+                "change_id": change_id,
+                "bids": bids,
+                "asks": asks,
+            });
+            if args.book_gap_rate > 0.0 && rand::thread_rng().gen_bool(args.book_gap_rate.min(1.0))
+            {
+                continue;
+            }
+            if !feed.push(data).await {
+                return;
+            }
+        }
+    });
+}
+
+/// Periodically push `trades`, `ticker` or `quote` notifications around a random-walk
+/// price until the connection goes away.
+fn spawn_market_feed(feed: Feed, kind: &'static str, instrument: String, interval: Duration) {
+    tokio::spawn(async move {
+        let args = Arc::clone(&feed.args);
+        let mut mid = args.base_price;
+        let mut trade_seq: i64 = 0;
+
+        loop {
+            sleep(interval).await;
+
+            let step = rand::thread_rng().gen_range(-1..=1) as f64;
+            mid += step * args.tick_size;
+            let bid = mid - args.tick_size;
+            let ask = mid + args.tick_size;
+            let timestamp = server_now_us(&args) / 1000;
+
+            let data = match kind {
+                "trades" => {
+                    let count = rand::thread_rng().gen_range(1..=3);
+                    let trades: Vec<Value> = (0..count)
+                        .map(|_| {
+                            trade_seq += 1;
+                            let buy = rand::thread_rng().gen_bool(0.5);
+                            json!({
+                                "trade_seq": trade_seq,
+                                "trade_id": format!("MOCK-T{trade_seq}"),
+                                "timestamp": timestamp,
+                                "instrument_name": instrument,
+                                "price": if buy { ask } else { bid },
+                                "amount": rand::thread_rng().gen_range(1..=20) as f64,
+                                "direction": if buy { "buy" } else { "sell" },
+                            })
+                        })
+                        .collect();
+                    json!(trades)
+                }
+                "ticker" => json!({
+                    "timestamp": timestamp,
+                    "instrument_name": instrument,
+                    "best_bid_price": bid,
+                    "best_bid_amount": 10.0,
+                    "best_ask_price": ask,
+                    "best_ask_amount": 10.0,
+                    "mark_price": mid,
+                    "index_price": mid,
+                    "last_price": mid,
+                    "state": "open",
+                }),
+                _ => json!({
+                    "timestamp": timestamp,
+                    "instrument_name": instrument,
+                    "best_bid_price": bid,
+                    "best_bid_amount": 10.0,
+                    "best_ask_price": ask,
+                    "best_ask_amount": 10.0,
+                }),
+            };
+            if !feed.push(data).await {
+                return;
+            }
+        }
+    });
+}
+
+/// Push the order changed by a successful `private/buy|sell|edit|cancel` to
+/// `user.orders.<instrument>.raw`, if subscribed, independently of the response.
+async fn notify_order_change(
+    method: &str,
+    result: &Value,
+    args: &Arc<Args>,
+    session: &Arc<Mutex<Session>>,
+    out_tx: &mpsc::UnboundedSender<Message>,
+) {
+    if !matches!(
+        method,
+        "private/buy" | "private/sell" | "private/edit" | "private/cancel"
+    ) {
+        return;
+    }
+    let order = result.get("order").unwrap_or(result).clone();
+    let Some(instrument) = order.get("instrument_name").and_then(|v| v.as_str()) else {
+        return;
+    };
+    let channel = format!("user.orders.{instrument}.raw");
+    let Some(active) = session.lock().await.subscriptions.get(&channel).cloned() else {
+        return;
+    };
+
+    let feed = Feed {
+        channel,
+        active,
+        args: Arc::clone(args),
+        out_tx: out_tx.clone(),
+    };
+    tokio::spawn(async move {
+        feed.push(order).await;
+    });
+}
+
+/// Send a `test_request` every `interval`; close the connection if the previous one
+/// was not answered with `public/test` (as Deribit does).
+fn spawn_heartbeat(
+    interval: Duration,
+    generation: u64,
+    session: Arc<Mutex<Session>>,
+    out_tx: mpsc::UnboundedSender<Message>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(interval).await;
+
+            let mut guard = session.lock().await;
+            if guard.heartbeat_generation != generation {
+                // Disabled or replaced by a newer set_heartbeat
+                return;
+            }
+            if guard.awaiting_test {
+                println!(
+                    "[{}] Heartbeat test_request unanswered, closing connection",
+                    Utc::now().to_rfc3339()
+                );
+                let _ = out_tx.send(Message::Close(None));
+                return;
+            }
+            guard.awaiting_test = true;
+
+            let msg = json!({
+                "jsonrpc": "2.0",
+                "method": "heartbeat",
+                "params": { "type": "test_request" },
+            });
+            if out_tx.send(Message::Text(msg.to_string())).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Current time of the mock's (possibly offset) clock in microseconds.
+fn server_now_us(args: &Args) -> i64 {
+    Utc::now().timestamp_micros() + args.clock_offset_us
+}
+
+fn subscription_message(channel: &str, data: Value) -> Message {
+    let msg = json!({
+        "jsonrpc": "2.0",
+        "method": "subscription",
+        "params": { "channel": channel, "data": data },
+    });
+    Message::Text(msg.to_string())
+}
+
+fn instrument_param(params: &Value) -> Result<&str, RpcError> {
+    str_param(params, "instrument_name")
+}
+
+/// Settlement currency of an instrument: `BTC-PERPETUAL` → `BTC`, `SOL_USDC-PERPETUAL` → `USDC`.
+fn settlement_currency(instrument: &str) -> &str {
+    let underlying = instrument.split('-').next().unwrap_or(instrument);
+    underlying
+        .split_once('_')
+        .map_or(underlying, |(_, quote)| quote)
+}
+
+fn str_param<'a>(params: &'a Value, key: &str) -> Result<&'a str, RpcError> {
+    params
+        .get(key)
+        .and_then(|v| v.as_str())
+        .ok_or(RpcError::INVALID_PARAMS)
+}
+
+fn f64_param(params: &Value, key: &str) -> Result<f64, RpcError> {
+    params
+        .get(key)
+        .and_then(|v| v.as_f64())
+        .ok_or(RpcError::INVALID_PARAMS)
+}
+
+/// Sleep for `duration` with microsecond precision: the last `SPIN_WINDOW` is spent
+/// yield-spinning on the clock.
+async fn precise_sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+    if duration > SPIN_WINDOW {
+        sleep_until(deadline - SPIN_WINDOW).await;
+    }
+    while Instant::now() < deadline {
+        tokio::task::yield_now().await;
+    }
+}
+
+fn one_way_delay(args: &Args) -> Duration {
+    jittered(args.latency_us, args.jitter_us)
+}
+
+fn jittered(base_us: u64, jitter_us: u64) -> Duration {
+    let jitter = if jitter_us > 0 {
+        rand::thread_rng().gen_range(0..=jitter_us)
+    } else {
+        0
+    };
+    Duration::from_micros(base_us + jitter)
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::json;
+use tokio::time::sleep;
+
+use crate::config::CancelOnDisconnectScope;
+use crate::deribit_client::DeribitClient;
+
+/// How long the order may stay open after the reconnect before the check fails.
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause between `private/get_open_orders_by_instrument` polls.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// One cancel-on-disconnect check: an order placed, the connection dropped and the order
+/// looked up again after reconnecting.
+#[derive(Debug, Serialize)]
+pub struct CancelOnDisconnectSample {
+    pub check: usize,
+    pub instrument_name: String,
+    pub order_id: String,
+    pub scope: String,
+    pub drop_ts_wall_iso: String,
+    /// Connection dropped → session re-established (including the reconnect backoff).
+    pub reconnect_us: i64,
+    /// The order was gone from the open orders after the reconnect.
+    pub cancelled: bool,
+    /// Connection dropped → first `get_open_orders_by_instrument` response without the order.
+    pub cleanup_us: Option<i64>,
+    /// `last_update_timestamp` of the cancelled order minus the local drop time, in ms
+    /// (Deribit's clock vs. the local clock; only if Deribit still reports the order).
+    pub cancel_delay_ms: Option<i64>,
+    /// `get_open_orders_by_instrument` requests until the order was gone.
+    pub polls: usize,
+}
+
+/// Drop the connection of `client` while `order_id` is open on `instrument_name`, wait for
+/// the reconnect and poll the open orders until the order is gone (or `VERIFY_TIMEOUT`
+/// passed).
+pub async fn check_order_cancelled(
+    client: &DeribitClient,
+    check: usize,
+    scope: CancelOnDisconnectScope,
+    instrument_name: &str,
+    order_id: &str,
+) -> Result<CancelOnDisconnectSample> {
+    let dropped = Instant::now();
+    let dropped_wall = Utc::now();
+    client.drop_connection().await;
+    client
+        .wait_connected()
+        .await
+        .context("reconnect after dropping the connection failed")?;
+    let reconnect_us = dropped.elapsed().as_micros() as i64;
+
+    let mut polls = 0;
+    let cleanup_us = loop {
+        polls += 1;
+        let params = json!({ "instrument_name": instrument_name });
+        let resp = client
+            .send_rpc("private/get_open_orders_by_instrument", params)
+            .await?;
+        let orders = resp
+            .result_or_error()
+            .context("get_open_orders_by_instrument failed")?;
+        let open = orders
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|o| o.get("order_id").and_then(|v| v.as_str()) == Some(order_id));
+        if !open {
+            break Some(resp.recv_ts_mono.duration_since(dropped).as_micros() as i64);
+        }
+        if dropped.elapsed() >= VERIFY_TIMEOUT {
+            break None;
+        }
+        sleep(POLL_INTERVAL).await;
+    };
+
+    // Deribit keeps reporting closed orders for a while, with the time they were cancelled
+    let mut cancel_delay_ms = None;
+    if cleanup_us.is_some() {
+        let resp = client
+            .send_rpc("private/get_order_state", json!({ "order_id": order_id }))
+            .await?;
+        if let Some(order) = resp.result.as_ref() {
+            if order.get("order_state").and_then(|v| v.as_str()) == Some("cancelled") {
+                cancel_delay_ms = order
+                    .get("last_update_timestamp")
+                    .and_then(|v| v.as_i64())
+                    .map(|ts| ts - dropped_wall.timestamp_millis());
+            }
+        }
+    }
+
+    Ok(CancelOnDisconnectSample {
+        check,
+        instrument_name: instrument_name.to_string(),
+        order_id: order_id.to_string(),
+        scope: scope.as_str().to_string(),
+        drop_ts_wall_iso: dropped_wall.to_rfc3339(),
+        reconnect_us,
+        cancelled: cleanup_us.is_some(),
+        cleanup_us,
+        cancel_delay_ms,
+        polls,
+    })
+}
@@ -0,0 +1,334 @@
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::{
+    CancelOnDisconnectScope, FileConfig, InstrumentFileConfig, InstrumentMode, OrderSide,
+    TickTriggerKind,
+};
+use crate::scenario::{ScenarioStep, StepAction};
+use crate::summary::{validate_quantiles, SummaryOptions, DEFAULT_QUANTILES};
+
+/// Config file used when `--config` is not given.
+pub const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+/// Command-line interface of the latency tester.
+#[derive(Debug, Parser)]
+#[command(
+    name = "deribit-latency-tester",
+    version,
+    about = "Deribit WebSocket latency tester"
+)]
+pub struct Cli {
+    /// Subcommand to execute. Defaults to `run` with `config.toml`.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run a latency test against Deribit.
+    Run(RunArgs),
+    /// Print summary statistics for an existing latency CSV.
+    Summarize {
+        /// Latency CSV written by a previous run.
+        csv: String,
+        /// Clock sync CSV of the same run, summarized after the latencies.
+        #[arg(long)]
+        clock_csv: Option<String>,
+        /// Market data feed latency CSV of the same run, summarized per channel.
+        #[arg(long)]
+        feed_csv: Option<String>,
+        /// Ack → user notification CSV of the same run.
+        #[arg(long)]
+        user_csv: Option<String>,
+        /// Results of a `cancel-on-disconnect-test`.
+        #[arg(long)]
+        cancel_on_disconnect_csv: Option<String>,
+        #[command(flatten)]
+        summary_args: SummaryArgs,
+    },
+    /// Compare two latency CSVs side by side.
+    Compare {
+        /// Baseline latency CSV.
+        baseline: String,
+        /// Candidate latency CSV compared against the baseline.
+        candidate: String,
+        #[command(flatten)]
+        summary_args: SummaryArgs,
+    },
+    /// Load and validate a config file (and credentials) without connecting.
+    ValidateConfig(RunArgs),
+    /// Check that cancel-on-disconnect cancels an open order when the connection drops
+    /// (`num_iterations` checks per instrument).
+    CancelOnDisconnectTest(RunArgs),
+}
+
+/// Summary settings of `summarize` and `compare` (`summary_*` in the config file).
+#[derive(Debug, Args)]
+pub struct SummaryArgs {
+    /// Quantiles to print, in percent.
+    #[arg(long, value_delimiter = ',', default_values_t = DEFAULT_QUANTILES)]
+    pub quantiles: Vec<f64>,
+    /// Print a log-bucketed histogram of the order RTTs.
+    #[arg(long, default_value_t = true, action = clap::ArgAction::Set)]
+    pub histogram: bool,
+}
+
+impl SummaryArgs {
+    pub fn options(&self) -> anyhow::Result<SummaryOptions> {
+        validate_quantiles(&self.quantiles)?;
+        Ok(SummaryOptions {
+            quantiles: self.quantiles.clone(),
+            histogram: self.histogram,
+        })
+    }
+}
+
+/// Config file location plus per-field overrides of its values.
+#[derive(Debug, Default, Args)]
+pub struct RunArgs {
+    /// Path to the TOML config file.
+    #[arg(long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: String,
+
+    #[arg(long)]
+    pub testnet: Option<bool>,
+    #[arg(long)]
+    pub endpoint_url: Option<String>,
+    #[arg(long)]
+    pub tls_ca_file: Option<String>,
+    #[arg(long)]
+    pub tls_server_name: Option<String>,
+    #[arg(long, value_enum)]
+    pub side: Option<OrderSide>,
+    #[arg(long)]
+    pub instrument_name: Option<String>,
+    #[arg(long)]
+    pub order_amount: Option<f64>,
+    #[arg(long, allow_negative_numbers = true)]
+    pub base_price: Option<f64>,
+    #[arg(long, allow_negative_numbers = true)]
+    pub price_offset_percent: Option<f64>,
+    #[arg(long, allow_negative_numbers = true)]
+    pub edit_offset_step_percent: Option<f64>,
+    #[arg(long)]
+    pub num_iterations: Option<usize>,
+    #[arg(long)]
+    pub sleep_between_requests_secs: Option<f64>,
+    #[arg(long)]
+    pub output_latency_csv: Option<String>,
+    #[arg(long)]
+    pub subscribe_raw_book: Option<bool>,
+    #[arg(long)]
+    pub print_summary: Option<bool>,
+    #[arg(long, value_delimiter = ',')]
+    pub summary_quantiles: Option<Vec<f64>>,
+    #[arg(long)]
+    pub summary_histogram: Option<bool>,
+    #[arg(long)]
+    pub reconnect: Option<bool>,
+    #[arg(long)]
+    pub reconnect_initial_backoff_ms: Option<u64>,
+    #[arg(long)]
+    pub reconnect_max_backoff_ms: Option<u64>,
+    #[arg(long)]
+    pub reconnect_max_attempts: Option<u32>,
+    #[arg(long)]
+    pub request_timeout_ms: Option<u64>,
+    #[arg(long)]
+    pub heartbeat_interval_secs: Option<u64>,
+    #[arg(long)]
+    pub cancel_on_disconnect: Option<bool>,
+    #[arg(long, value_enum)]
+    pub cancel_on_disconnect_scope: Option<CancelOnDisconnectScope>,
+    #[arg(long)]
+    pub output_cancel_on_disconnect_csv: Option<String>,
+    #[arg(long)]
+    pub concurrent_chains: Option<usize>,
+    #[arg(long)]
+    pub clock_sync_samples: Option<usize>,
+    #[arg(long)]
+    pub clock_probe_interval_ms: Option<u64>,
+    #[arg(long)]
+    pub output_clock_csv: Option<String>,
+    #[arg(long)]
+    pub tick_to_trade: Option<bool>,
+    #[arg(long, value_enum)]
+    pub tick_trigger: Option<TickTriggerKind>,
+    #[arg(long)]
+    pub tick_trigger_every_n: Option<usize>,
+    #[arg(long)]
+    pub tick_trigger_min_price_move: Option<f64>,
+    #[arg(long)]
+    pub price_from_book: Option<bool>,
+    #[arg(long, value_delimiter = ',')]
+    pub feed_channels: Option<Vec<String>>,
+    #[arg(long)]
+    pub output_feed_csv: Option<String>,
+    #[arg(long)]
+    pub resync_on_book_gap: Option<bool>,
+    #[arg(long)]
+    pub subscribe_user_channels: Option<bool>,
+    #[arg(long)]
+    pub output_user_csv: Option<String>,
+    /// Instruments traded with the top-level order parameters (replaces `[[instruments]]`).
+    #[arg(long, value_delimiter = ',')]
+    pub instruments: Option<Vec<String>>,
+    #[arg(long, value_enum)]
+    pub instrument_mode: Option<InstrumentMode>,
+    /// Scenario steps with default settings (replaces `[[scenario.steps]]`).
+    #[arg(long, value_enum, value_delimiter = ',')]
+    pub scenario_steps: Option<Vec<StepAction>>,
+    #[arg(long)]
+    pub order_label: Option<String>,
+    #[arg(long)]
+    pub cancel_stale_orders: Option<bool>,
+}
+
+impl RunArgs {
+    /// Default arguments used when no subcommand is given.
+    pub fn with_default_config() -> Self {
+        Self {
+            config: DEFAULT_CONFIG_PATH.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Apply command-line overrides on top of the values read from the config file.
+    pub fn apply_overrides(&self, file_cfg: &mut FileConfig) {
+        if let Some(v) = self.testnet {
+            file_cfg.testnet = v;
+        }
+        if let Some(v) = &self.endpoint_url {
+            file_cfg.endpoint_url = Some(v.clone());
+        }
+        if let Some(v) = &self.tls_ca_file {
+            file_cfg.tls_ca_file = Some(v.clone());
+        }
+        if let Some(v) = &self.tls_server_name {
+            file_cfg.tls_server_name = Some(v.clone());
+        }
+        if let Some(v) = self.side {
+            file_cfg.side = v;
+        }
+        if let Some(v) = &self.instrument_name {
+            file_cfg.instrument_name = v.clone();
+        }
+        if let Some(v) = self.order_amount {
+            file_cfg.order_amount = v;
+        }
+        if let Some(v) = self.base_price {
+            file_cfg.base_price = v;
+        }
+        if let Some(v) = self.price_offset_percent {
+            file_cfg.price_offset_percent = v;
+        }
+        if let Some(v) = self.edit_offset_step_percent {
+            file_cfg.edit_offset_step_percent = v;
+        }
+        if let Some(v) = self.num_iterations {
+            file_cfg.num_iterations = v;
+        }
+        if let Some(v) = self.sleep_between_requests_secs {
+            file_cfg.sleep_between_requests_secs = v;
+        }
+        if let Some(v) = &self.output_latency_csv {
+            file_cfg.output_latency_csv = v.clone();
+        }
+        if let Some(v) = self.subscribe_raw_book {
+            file_cfg.subscribe_raw_book = v;
+        }
+        if let Some(v) = self.print_summary {
+            file_cfg.print_summary = v;
+        }
+        if let Some(v) = &self.summary_quantiles {
+            file_cfg.summary_quantiles = v.clone();
+        }
+        if let Some(v) = self.summary_histogram {
+            file_cfg.summary_histogram = v;
+        }
+        if let Some(v) = self.reconnect {
+            file_cfg.reconnect = v;
+        }
+        if let Some(v) = self.reconnect_initial_backoff_ms {
+            file_cfg.reconnect_initial_backoff_ms = v;
+        }
+        if let Some(v) = self.reconnect_max_backoff_ms {
+            file_cfg.reconnect_max_backoff_ms = v;
+        }
+        if let Some(v) = self.reconnect_max_attempts {
+            file_cfg.reconnect_max_attempts = v;
+        }
+        if let Some(v) = self.request_timeout_ms {
+            file_cfg.request_timeout_ms = v;
+        }
+        if let Some(v) = self.heartbeat_interval_secs {
+            file_cfg.heartbeat_interval_secs = v;
+        }
+        if let Some(v) = self.cancel_on_disconnect {
+            file_cfg.cancel_on_disconnect = v;
+        }
+        if let Some(v) = self.cancel_on_disconnect_scope {
+            file_cfg.cancel_on_disconnect_scope = v;
+        }
+        if let Some(v) = &self.output_cancel_on_disconnect_csv {
+            file_cfg.output_cancel_on_disconnect_csv = v.clone();
+        }
+        if let Some(v) = self.concurrent_chains {
+            file_cfg.concurrent_chains = v;
+        }
+        if let Some(v) = self.clock_sync_samples {
+            file_cfg.clock_sync_samples = v;
+        }
+        if let Some(v) = self.clock_probe_interval_ms {
+            file_cfg.clock_probe_interval_ms = v;
+        }
+        if let Some(v) = &self.output_clock_csv {
+            file_cfg.output_clock_csv = v.clone();
+        }
+        if let Some(v) = self.tick_to_trade {
+            file_cfg.tick_to_trade = v;
+        }
+        if let Some(v) = self.tick_trigger {
+            file_cfg.tick_trigger = v;
+        }
+        if let Some(v) = self.tick_trigger_every_n {
+            file_cfg.tick_trigger_every_n = v;
+        }
+        if let Some(v) = self.tick_trigger_min_price_move {
+            file_cfg.tick_trigger_min_price_move = v;
+        }
+        if let Some(v) = self.price_from_book {
+            file_cfg.price_from_book = v;
+        }
+        if let Some(v) = &self.feed_channels {
+            file_cfg.feed_channels = v.clone();
+        }
+        if let Some(v) = &self.output_feed_csv {
+            file_cfg.output_feed_csv = v.clone();
+        }
+        if let Some(v) = self.resync_on_book_gap {
+            file_cfg.resync_on_book_gap = v;
+        }
+        if let Some(v) = self.subscribe_user_channels {
+            file_cfg.subscribe_user_channels = v;
+        }
+        if let Some(v) = &self.output_user_csv {
+            file_cfg.output_user_csv = v.clone();
+        }
+        if let Some(v) = &self.instruments {
+            file_cfg.instruments = v.iter().map(|n| InstrumentFileConfig::named(n)).collect();
+        }
+        if let Some(v) = self.instrument_mode {
+            file_cfg.instrument_mode = v;
+        }
+        if let Some(v) = &self.scenario_steps {
+            file_cfg.scenario.steps = v.iter().map(|a| ScenarioStep::new(*a)).collect();
+        }
+        if let Some(v) = &self.order_label {
+            file_cfg.order_label = v.clone();
+        }
+        if let Some(v) = self.cancel_stale_orders {
+            file_cfg.cancel_stale_orders = v;
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use csv::Writer;
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+
+use crate::deribit_client::DeribitClient;
+use crate::latency::{create_csv_writer, LatencyLogger};
+
+/// Number of most recent offset samples the estimate is picked from.
+const OFFSET_WINDOW: usize = 64;
+
+/// One NTP-style offset measurement from a single request/response pair.
+#[derive(Debug, Clone, Copy)]
+pub struct OffsetSample {
+    /// Deribit clock minus local wall clock, in microseconds.
+    pub offset_us: i64,
+    /// Round trip minus server processing time (`usOut - usIn`), in microseconds.
+    pub delay_us: i64,
+}
+
+impl OffsetSample {
+    /// Offset and delay from local send/receive wall times and the engine `usIn` / `usOut`.
+    pub fn from_exchange(
+        send_wall: DateTime<Utc>,
+        us_in: i64,
+        us_out: i64,
+        recv_wall: DateTime<Utc>,
+    ) -> Self {
+        let t0 = send_wall.timestamp_micros();
+        let t3 = recv_wall.timestamp_micros();
+        Self {
+            offset_us: ((us_in - t0) + (us_out - t3)) / 2,
+            delay_us: (t3 - t0) - (us_out - us_in),
+        }
+    }
+}
+
+/// Current best estimate of the clock offset.
+#[derive(Debug, Clone, Copy)]
+pub struct OffsetEstimate {
+    /// Deribit clock minus local wall clock, in microseconds.
+    pub offset_us: i64,
+    /// The true offset lies within `offset_us ± uncertainty_us` (half the sample's delay).
+    pub uncertainty_us: i64,
+}
+
+/// Tracks the offset between the local wall clock and Deribit's clock.
+///
+/// Uses min-RTT filtering: of the recent samples, the one with the smallest network
+/// delay bounds the offset most tightly and is used as the estimate.
+#[derive(Debug, Default)]
+pub struct ClockOffsetEstimator {
+    samples: VecDeque<OffsetSample>,
+}
+
+impl ClockOffsetEstimator {
+    pub fn add(&mut self, sample: OffsetSample) {
+        // Negative delays mean the local clock jumped; such samples bound nothing
+        if sample.delay_us < 0 {
+            return;
+        }
+        if self.samples.len() == OFFSET_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn estimate(&self) -> Option<OffsetEstimate> {
+        self.samples
+            .iter()
+            .min_by_key(|s| s.delay_us)
+            .map(|s| OffsetEstimate {
+                offset_us: s.offset_us,
+                uncertainty_us: s.delay_us / 2,
+            })
+    }
+}
+
+/// One `public/get_time` probe as written to the clock sync CSV.
+#[derive(Debug, Serialize)]
+pub struct ClockProbeSample {
+    /// `pre_run` (initial burst) or `run` (periodic probe during the run).
+    pub phase: &'static str,
+
+    pub send_ts_mono_ns: i64,
+    pub recv_ts_mono_ns: i64,
+    pub send_ts_wall_iso: String,
+    pub recv_ts_wall_iso: String,
+    pub rtt_us: i64,
+
+    /// `public/get_time` result (Deribit clock, milliseconds).
+    pub server_time_ms: Option<i64>,
+    pub engine_us_in: i64,
+    pub engine_us_out: i64,
+
+    /// NTP-style offset (Deribit − local) and network delay of this probe alone.
+    pub offset_us: i64,
+    pub delay_us: i64,
+
+    /// Min-RTT filtered estimate after this probe; the true offset lies within ± uncertainty.
+    pub est_offset_us: Option<i64>,
+    pub est_uncertainty_us: Option<i64>,
+
+    /// Change of the estimated offset since the end of the initial burst, in µs per second
+    /// of local time (ppm), and its uncertainty.
+    pub drift_ppm: Option<f64>,
+    pub drift_uncertainty_ppm: Option<f64>,
+}
+
+/// Probes Deribit's clock with `public/get_time` and logs every probe to its own CSV.
+///
+/// Probes feed the latency logger's offset estimator, which is also used for the
+/// one-way latency estimates of the order samples.
+pub struct ClockProbe {
+    client: DeribitClient,
+    logger: Arc<Mutex<LatencyLogger>>,
+    writer: Writer<File>,
+    program_start: Instant,
+    /// Estimate at the end of the initial burst, for drift.
+    baseline: Option<(DateTime<Utc>, OffsetEstimate)>,
+}
+
+impl ClockProbe {
+    pub fn new(
+        csv_path: &str,
+        client: DeribitClient,
+        logger: Arc<Mutex<LatencyLogger>>,
+        program_start: Instant,
+    ) -> Result<Self> {
+        Ok(Self {
+            client,
+            logger,
+            writer: create_csv_writer(csv_path)?,
+            program_start,
+            baseline: None,
+        })
+    }
+
+    /// Probe `samples` times back to back and remember the resulting estimate as drift baseline.
+    pub async fn initial_burst(&mut self, samples: usize) -> Result<Option<OffsetEstimate>> {
+        let mut estimate = None;
+        for _ in 0..samples {
+            estimate = self.probe("pre_run").await?;
+        }
+        self.baseline = estimate.map(|est| (Utc::now(), est));
+        Ok(estimate)
+    }
+
+    /// Probe every `interval` until the task is aborted. Failed probes (e.g. while
+    /// reconnecting) are reported and skipped.
+    pub fn spawn_periodic(mut self, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                sleep(interval).await;
+                if let Err(e) = self.probe("run").await {
+                    eprintln!("Clock probe failed: {e}");
+                }
+            }
+        })
+    }
+
+    /// One `public/get_time` round trip; returns the updated offset estimate.
+    async fn probe(&mut self, phase: &'static str) -> Result<Option<OffsetEstimate>> {
+        let pending = self.client.send("public/get_time", json!({})).await?;
+        let sent = pending.sent;
+        let resp = pending.response().await?;
+        let server_time_ms = resp.result_or_error()?.as_i64();
+
+        // `usIn` / `usOut` bracket the server processing; fall back to the millisecond result
+        let engine_ts = |key: &str| resp.raw.get(key).and_then(|v| v.as_i64());
+        let (us_in, us_out) = match (engine_ts("usIn"), engine_ts("usOut"), server_time_ms) {
+            (Some(us_in), Some(us_out), _) => (us_in, us_out),
+            (_, _, Some(ms)) => (ms * 1000, ms * 1000),
+            _ => return Err(anyhow!("get_time response has no server timestamp")),
+        };
+
+        let sample =
+            OffsetSample::from_exchange(sent.written_wall, us_in, us_out, resp.recv_ts_wall);
+        let estimate = self.logger.lock().await.record_clock_sample(sample);
+
+        let drift = match (self.baseline, estimate) {
+            (Some((base_ts, base)), Some(est)) => {
+                let elapsed_us = (resp.recv_ts_wall - base_ts)
+                    .num_microseconds()
+                    .unwrap_or(0);
+                (elapsed_us >= 1_000_000).then(|| {
+                    let per_us = 1e6 / elapsed_us as f64;
+                    (
+                        (est.offset_us - base.offset_us) as f64 * per_us,
+                        (est.uncertainty_us + base.uncertainty_us) as f64 * per_us,
+                    )
+                })
+            }
+            _ => None,
+        };
+
+        let row = ClockProbeSample {
+            phase,
+            send_ts_mono_ns: self.ns_since_start(sent.written_mono),
+            recv_ts_mono_ns: self.ns_since_start(resp.recv_ts_mono),
+            send_ts_wall_iso: sent.written_wall.to_rfc3339(),
+            recv_ts_wall_iso: resp.recv_ts_wall.to_rfc3339(),
+            rtt_us: resp
+                .recv_ts_mono
+                .duration_since(sent.written_mono)
+                .as_micros() as i64,
+            server_time_ms,
+            engine_us_in: us_in,
+            engine_us_out: us_out,
+            offset_us: sample.offset_us,
+            delay_us: sample.delay_us,
+            est_offset_us: estimate.map(|est| est.offset_us),
+            est_uncertainty_us: estimate.map(|est| est.uncertainty_us),
+            drift_ppm: drift.map(|(ppm, _)| ppm),
+            drift_uncertainty_ppm: drift.map(|(_, unc)| unc),
+        };
+        self.writer.serialize(row)?;
+        self.writer.flush()?;
+
+        Ok(estimate)
+    }
+
+    fn ns_since_start(&self, t: Instant) -> i64 {
+        t.duration_since(self.program_start).as_nanos() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn wall(us: i64) -> DateTime<Utc> {
+        Utc.timestamp_micros(us).unwrap()
+    }
+
+    #[test]
+    fn from_exchange_symmetric_path() {
+        // Deribit 1000us ahead, 500us each way, 200us in the engine
+        let t0 = 1_763_712_844_000_000;
+        let sample =
+            OffsetSample::from_exchange(wall(t0), t0 + 1_500, t0 + 1_700, wall(t0 + 1_200));
+        assert_eq!(sample.offset_us, 1_000);
+        assert_eq!(sample.delay_us, 1_000);
+    }
+
+    #[test]
+    fn from_exchange_asymmetric_path_splits_the_difference() {
+        // Deribit 300us behind, 900us out and 100us back: the estimate is off by half the
+        // asymmetry, which the delay (and so the uncertainty) bounds
+        let t0 = 1_763_712_844_000_000;
+        let us_in = t0 - 300 + 900;
+        let us_out = us_in + 50;
+        let t3 = us_out + 300 + 100;
+        let sample = OffsetSample::from_exchange(wall(t0), us_in, us_out, wall(t3));
+        assert_eq!(sample.offset_us, 100);
+        assert_eq!(sample.delay_us, 1_000);
+        assert!((sample.offset_us - -300).abs() <= sample.delay_us / 2);
+    }
+}
@@ -2,20 +2,111 @@ use std::fs;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
+use chrono::Utc;
 use serde::Deserialize;
 
+use crate::scenario::{
+    default_steps, validate_step_concurrency, validate_steps, ScenarioFileConfig, ScenarioStep,
+};
+use crate::summary::{validate_quantiles, SummaryOptions, DEFAULT_QUANTILES};
+
+/// Maximum length of a Deribit order label.
+const MAX_ORDER_LABEL_LEN: usize = 64;
+/// Shortest heartbeat interval `public/set_heartbeat` accepts, in seconds.
+const MIN_HEARTBEAT_INTERVAL_SECS: u64 = 10;
+
 /// Side of the order to place.
-#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
 #[serde(rename_all = "lowercase")]
 pub enum OrderSide {
     Buy,
     Sell,
 }
 
+/// Which raw book ticks fire orders in tick-to-trade mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum TickTriggerKind {
+    /// Every `tick_trigger_every_n`-th tick.
+    EveryNth,
+    /// Ticks that change the best bid or best ask.
+    TopOfBook,
+    /// Ticks that move the mid price by at least `tick_trigger_min_price_move`.
+    PriceMove,
+}
+
+/// How the orders of several instruments are scheduled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum InstrumentMode {
+    /// Iterations rotate through the instruments (one shared pool of chains).
+    RoundRobin,
+    /// Every instrument runs its own chains, all instruments at the same time.
+    Parallel,
+}
+
+/// Scope of `private/enable_cancel_on_disconnect`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum CancelOnDisconnectScope {
+    /// Only the tester's connection (enabled again after every reconnect).
+    Connection,
+    /// Every connection of the account; the setting stays on after the run.
+    Account,
+}
+
+impl CancelOnDisconnectScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CancelOnDisconnectScope::Connection => "connection",
+            CancelOnDisconnectScope::Account => "account",
+        }
+    }
+}
+
+/// One `[[instruments]]` entry; unset order parameters fall back to the top-level values.
+#[derive(Debug, Clone, Deserialize)]
+pub struct InstrumentFileConfig {
+    pub instrument_name: String,
+    #[serde(default)]
+    pub side: Option<OrderSide>,
+    #[serde(default)]
+    pub order_amount: Option<f64>,
+    #[serde(default)]
+    pub base_price: Option<f64>,
+    #[serde(default)]
+    pub price_offset_percent: Option<f64>,
+    #[serde(default)]
+    pub edit_offset_step_percent: Option<f64>,
+}
+
+impl InstrumentFileConfig {
+    /// Entry using the top-level order parameters for `instrument_name`.
+    pub fn named(instrument_name: &str) -> Self {
+        Self {
+            instrument_name: instrument_name.to_string(),
+            side: None,
+            order_amount: None,
+            base_price: None,
+            price_offset_percent: None,
+            edit_offset_step_percent: None,
+        }
+    }
+}
+
 /// Configuration as defined in `config.toml` (without secrets).
 #[derive(Debug, Deserialize)]
 pub struct FileConfig {
     pub testnet: bool,
+    #[serde(default)]
+    pub endpoint_url: Option<String>,
+    #[serde(default)]
+    pub tls_ca_file: Option<String>,
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
     pub side: OrderSide,
     pub instrument_name: String,
     pub order_amount: f64,
@@ -27,6 +118,104 @@ pub struct FileConfig {
     pub output_latency_csv: String,
     pub subscribe_raw_book: bool,
     pub print_summary: bool,
+    #[serde(default = "default_summary_quantiles")]
+    pub summary_quantiles: Vec<f64>,
+    #[serde(default = "default_true")]
+    pub summary_histogram: bool,
+    #[serde(default = "default_true")]
+    pub reconnect: bool,
+    #[serde(default = "default_reconnect_initial_backoff_ms")]
+    pub reconnect_initial_backoff_ms: u64,
+    #[serde(default = "default_reconnect_max_backoff_ms")]
+    pub reconnect_max_backoff_ms: u64,
+    #[serde(default)]
+    pub reconnect_max_attempts: u32,
+    #[serde(default = "default_request_timeout_ms")]
+    pub request_timeout_ms: u64,
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    #[serde(default)]
+    pub cancel_on_disconnect: bool,
+    #[serde(default = "default_cancel_on_disconnect_scope")]
+    pub cancel_on_disconnect_scope: CancelOnDisconnectScope,
+    #[serde(default = "default_output_cancel_on_disconnect_csv")]
+    pub output_cancel_on_disconnect_csv: String,
+    #[serde(default = "default_concurrent_chains")]
+    pub concurrent_chains: usize,
+    #[serde(default = "default_clock_sync_samples")]
+    pub clock_sync_samples: usize,
+    #[serde(default = "default_clock_probe_interval_ms")]
+    pub clock_probe_interval_ms: u64,
+    #[serde(default = "default_output_clock_csv")]
+    pub output_clock_csv: String,
+    #[serde(default)]
+    pub tick_to_trade: bool,
+    #[serde(default = "default_tick_trigger")]
+    pub tick_trigger: TickTriggerKind,
+    #[serde(default = "default_tick_trigger_every_n")]
+    pub tick_trigger_every_n: usize,
+    #[serde(default)]
+    pub tick_trigger_min_price_move: f64,
+    #[serde(default = "default_true")]
+    pub price_from_book: bool,
+    #[serde(default)]
+    pub feed_channels: Vec<String>,
+    #[serde(default = "default_output_feed_csv")]
+    pub output_feed_csv: String,
+    #[serde(default = "default_true")]
+    pub resync_on_book_gap: bool,
+    #[serde(default)]
+    pub subscribe_user_channels: bool,
+    #[serde(default = "default_output_user_csv")]
+    pub output_user_csv: String,
+    #[serde(default)]
+    pub instruments: Vec<InstrumentFileConfig>,
+    #[serde(default = "default_instrument_mode")]
+    pub instrument_mode: InstrumentMode,
+    #[serde(default)]
+    pub scenario: ScenarioFileConfig,
+    #[serde(default = "default_order_label")]
+    pub order_label: String,
+    #[serde(default)]
+    pub cancel_stale_orders: bool,
+}
+
+impl FileConfig {
+    /// Read and parse a TOML config file without resolving credentials.
+    pub fn load_from_file(path: &str) -> Result<Self> {
+        let raw = fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file at '{}'", path))?;
+
+        toml::from_str(&raw).with_context(|| format!("failed to parse config file at '{}'", path))
+    }
+}
+
+/// Resolved order parameters of one traded instrument.
+#[derive(Debug, Clone)]
+pub struct InstrumentConfig {
+    pub instrument_name: String,
+    pub side: OrderSide,
+    pub order_amount: f64,
+    pub base_price: f64,
+    pub price_offset_percent: f64,
+    pub edit_offset_step_percent: f64,
+}
+
+impl InstrumentConfig {
+    /// Settlement currency of the instrument (`BTC`, `ETH`, `SOL`, `USDC`, ...).
+    pub fn currency(&self) -> &str {
+        instrument_currency(&self.instrument_name)
+    }
+}
+
+/// Settlement currency encoded in an instrument name: `BTC-PERPETUAL` → `BTC`,
+/// `ETH-27DEC24-3000-C` → `ETH`, linear `SOL_USDC-PERPETUAL` → `USDC`.
+pub fn instrument_currency(instrument_name: &str) -> &str {
+    let underlying = instrument_name.split('-').next().unwrap_or(instrument_name);
+    match underlying.split_once('_') {
+        Some((_, quote)) => quote,
+        None => underlying,
+    }
 }
 
 /// Fully resolved configuration used by the latency tester.
@@ -34,36 +223,104 @@ pub struct FileConfig {
 #[derive(Debug, Clone)]
 pub struct Config {
     pub testnet: bool,
+    /// WebSocket URL to connect to (`ws://` or `wss://`).
+    pub endpoint_url: String,
+    /// Optional PEM file with extra CA roots trusted for `wss://` endpoints.
+    pub tls_ca_file: Option<String>,
+    /// Optional TLS server name (SNI) overriding the host of `endpoint_url`.
+    pub tls_server_name: Option<String>,
     pub client_id: String,
     pub client_secret: String,
 
-    pub side: OrderSide,
-    pub instrument_name: String,
-    pub order_amount: f64,
-    pub base_price: f64,
-    pub price_offset_percent: f64,
-    pub edit_offset_step_percent: f64,
+    /// Traded instruments: the `[[instruments]]` entries, or the top-level instrument
+    /// alone (never empty).
+    pub instruments: Vec<InstrumentConfig>,
+    pub instrument_mode: InstrumentMode,
+
+    /// Steps of every iteration: the `[[scenario.steps]]`, or open → edit → cancel.
+    pub scenario_steps: Vec<ScenarioStep>,
 
+    /// Label prefix of the tester's orders; leftovers of earlier runs carry it too.
+    pub order_label: String,
+    /// Label of every order placed by this run: `<order_label>-<run id>`.
+    pub run_label: String,
+    /// Cancel open orders labelled `<order_label>-*` at startup (leftovers of earlier runs,
+    /// but also the orders of other testers running with the same `order_label`).
+    pub cancel_stale_orders: bool,
+
+    /// Iterations per instrument.
     pub num_iterations: usize,
     pub sleep_between_requests: Duration,
 
     pub output_latency_csv: String,
     pub subscribe_raw_book: bool,
     pub print_summary: bool,
+    /// Quantiles and histogram of the printed summaries.
+    pub summary: SummaryOptions,
+
+    /// Automatically reconnect (with exponential backoff) when the connection drops.
+    pub reconnect: bool,
+    pub reconnect_initial_backoff: Duration,
+    pub reconnect_max_backoff: Duration,
+    /// Consecutive failed reconnect attempts before giving up (0 = unlimited).
+    pub reconnect_max_attempts: u32,
+
+    /// Maximum time to wait for each RPC response (`None` = wait forever).
+    pub request_timeout: Option<Duration>,
+
+    /// Interval requested via `public/set_heartbeat` (`None` = no heartbeats).
+    pub heartbeat_interval: Option<Duration>,
+
+    /// Enable `private/enable_cancel_on_disconnect` after every authentication, so Deribit
+    /// cancels the orders when the connection drops.
+    pub cancel_on_disconnect: bool,
+    pub cancel_on_disconnect_scope: CancelOnDisconnectScope,
+    /// CSV receiving the results of `cancel-on-disconnect-test`.
+    pub output_cancel_on_disconnect_csv: String,
+
+    /// Number of independent open → edit → cancel chains run concurrently.
+    pub concurrent_chains: usize,
+
+    /// `public/get_time` round trips used to estimate the clock offset before the run.
+    pub clock_sync_samples: usize,
+    /// Interval of `public/get_time` probes during the run (`None` = no probes).
+    pub clock_probe_interval: Option<Duration>,
+    /// CSV receiving every clock probe.
+    pub output_clock_csv: String,
+
+    /// Fire each open / edit on a raw book tick instead of the fixed sleep cadence.
+    pub tick_to_trade: bool,
+    pub tick_trigger: TickTriggerKind,
+    pub tick_trigger_every_n: usize,
+    /// Minimum mid price move (in price units) for `TickTriggerKind::PriceMove`.
+    pub tick_trigger_min_price_move: f64,
+
+    /// Price orders off the live local book (needs `subscribe_raw_book`) instead of the
+    /// ticker price fetched at startup.
+    pub price_from_book: bool,
+
+    /// Extra market data channels subscribed for feed latency measurement
+    /// (`{instrument}` replaced by every traded instrument).
+    pub feed_channels: Vec<String>,
+    /// CSV receiving the feed latency of every timestamped market data notification.
+    pub output_feed_csv: String,
+
+    /// Resubscribe a raw book channel (for a fresh snapshot) after a `change_id` gap.
+    pub resync_on_book_gap: bool,
+
+    /// Subscribe `user.orders.<instrument>.raw` / `user.trades.<instrument>.raw` and pair
+    /// every order ack with its notification.
+    pub subscribe_user_channels: bool,
+    /// CSV receiving the ack → user notification delays.
+    pub output_user_csv: String,
 }
 
 impl Config {
-    /// Load configuration from a TOML file and environment variables.
+    /// Resolve a (possibly overridden) [`FileConfig`] into the final configuration.
     ///
-    /// * Non-secret values are read from `path`.
+    /// * Non-secret values are taken from `file_cfg` (see [`FileConfig::load_from_file`]).
     /// * `DERIBIT_CLIENT_ID` and `DERIBIT_CLIENT_SECRET` are read from the process environment.
-    pub fn load_from_file(path: &str) -> Result<Self> {
-        let raw = fs::read_to_string(path)
-            .with_context(|| format!("failed to read config file at '{}'", path))?;
-
-        let file_cfg: FileConfig = toml::from_str(&raw)
-            .with_context(|| format!("failed to parse config file at '{}'", path))?;
-
+    pub fn from_file_config(file_cfg: FileConfig) -> Result<Self> {
         let client_id = std::env::var("DERIBIT_CLIENT_ID")
             .with_context(|| "DERIBIT_CLIENT_ID environment variable is not set")?;
         let client_secret = std::env::var("DERIBIT_CLIENT_SECRET")
@@ -73,21 +330,286 @@ impl Config {
             anyhow::bail!("Deribit credentials must not be empty");
         }
 
+        let instruments = resolve_instruments(&file_cfg)?;
+        validate_steps(&file_cfg.scenario.steps)?;
+        validate_step_concurrency(
+            &file_cfg.scenario.steps,
+            file_cfg.concurrent_chains,
+            file_cfg.instrument_mode == InstrumentMode::Parallel && instruments.len() > 1,
+        )?;
+        validate_quantiles(&file_cfg.summary_quantiles)?;
+
+        if file_cfg.order_label.is_empty() {
+            anyhow::bail!("order_label must not be empty");
+        }
+        // The random suffix keeps runs started in the same second apart
+        let run_label = format!(
+            "{}-{}-{:06x}",
+            file_cfg.order_label,
+            Utc::now().format("%Y%m%dT%H%M%S"),
+            rand::random::<u32>() & 0xff_ffff
+        );
+        // Deribit accepts labels of up to 64 characters, including step labels
+        let longest_step_label = file_cfg
+            .scenario
+            .steps
+            .iter()
+            .filter_map(|s| s.label.as_ref())
+            .map(|l| l.len() + 1)
+            .max()
+            .unwrap_or(0);
+        if run_label.len() + longest_step_label > MAX_ORDER_LABEL_LEN {
+            anyhow::bail!(
+                "order labels must not exceed {} characters (got '{}' plus scenario labels)",
+                MAX_ORDER_LABEL_LEN,
+                run_label
+            );
+        }
+
+        let endpoint_url = file_cfg
+            .endpoint_url
+            .unwrap_or_else(|| default_endpoint_url(file_cfg.testnet).to_string());
+        if !endpoint_url.starts_with("ws://") && !endpoint_url.starts_with("wss://") {
+            anyhow::bail!(
+                "endpoint_url must start with ws:// or wss:// (got '{}')",
+                endpoint_url
+            );
+        }
+
+        if !file_cfg.sleep_between_requests_secs.is_finite()
+            || file_cfg.sleep_between_requests_secs < 0.0
+        {
+            anyhow::bail!(
+                "sleep_between_requests_secs must be a non-negative number (got {})",
+                file_cfg.sleep_between_requests_secs
+            );
+        }
+        if file_cfg.concurrent_chains == 0 {
+            anyhow::bail!("concurrent_chains must be at least 1");
+        }
+        if (1..MIN_HEARTBEAT_INTERVAL_SECS).contains(&file_cfg.heartbeat_interval_secs) {
+            anyhow::bail!(
+                "heartbeat_interval_secs must be 0 (disabled) or at least {} (Deribit's minimum, got {})",
+                MIN_HEARTBEAT_INTERVAL_SECS,
+                file_cfg.heartbeat_interval_secs
+            );
+        }
+        if file_cfg.reconnect_initial_backoff_ms == 0 {
+            anyhow::bail!("reconnect_initial_backoff_ms must be at least 1");
+        }
+        if file_cfg.reconnect_initial_backoff_ms > file_cfg.reconnect_max_backoff_ms {
+            anyhow::bail!(
+                "reconnect_initial_backoff_ms ({}) must not exceed reconnect_max_backoff_ms ({})",
+                file_cfg.reconnect_initial_backoff_ms,
+                file_cfg.reconnect_max_backoff_ms
+            );
+        }
+        if file_cfg.tick_to_trade && !file_cfg.subscribe_raw_book {
+            anyhow::bail!("tick_to_trade requires subscribe_raw_book = true");
+        }
+        if file_cfg.tick_trigger_every_n == 0 {
+            anyhow::bail!("tick_trigger_every_n must be at least 1");
+        }
+        if !file_cfg.tick_trigger_min_price_move.is_finite()
+            || file_cfg.tick_trigger_min_price_move < 0.0
+        {
+            anyhow::bail!(
+                "tick_trigger_min_price_move must be a non-negative number (got {})",
+                file_cfg.tick_trigger_min_price_move
+            );
+        }
+
+        // Templates with {instrument} are subscribed once per instrument
+        let mut feed_channels: Vec<String> = Vec::new();
+        for template in &file_cfg.feed_channels {
+            for instrument in &instruments {
+                let channel = template.replace("{instrument}", &instrument.instrument_name);
+                if !feed_channels.contains(&channel) {
+                    feed_channels.push(channel);
+                }
+            }
+        }
+
         Ok(Self {
             testnet: file_cfg.testnet,
+            endpoint_url,
+            tls_ca_file: file_cfg.tls_ca_file,
+            tls_server_name: file_cfg.tls_server_name,
             client_id,
             client_secret,
-            side: file_cfg.side,
-            instrument_name: file_cfg.instrument_name,
-            order_amount: file_cfg.order_amount,
-            base_price: file_cfg.base_price,
-            price_offset_percent: file_cfg.price_offset_percent,
-            edit_offset_step_percent: file_cfg.edit_offset_step_percent,
+            instruments,
+            instrument_mode: file_cfg.instrument_mode,
+            scenario_steps: if file_cfg.scenario.steps.is_empty() {
+                default_steps()
+            } else {
+                file_cfg.scenario.steps
+            },
+            order_label: file_cfg.order_label,
+            run_label,
+            cancel_stale_orders: file_cfg.cancel_stale_orders,
             num_iterations: file_cfg.num_iterations,
             sleep_between_requests: Duration::from_secs_f64(file_cfg.sleep_between_requests_secs),
             output_latency_csv: file_cfg.output_latency_csv,
             subscribe_raw_book: file_cfg.subscribe_raw_book,
             print_summary: file_cfg.print_summary,
+            summary: SummaryOptions {
+                quantiles: file_cfg.summary_quantiles,
+                histogram: file_cfg.summary_histogram,
+            },
+            reconnect: file_cfg.reconnect,
+            reconnect_initial_backoff: Duration::from_millis(file_cfg.reconnect_initial_backoff_ms),
+            reconnect_max_backoff: Duration::from_millis(file_cfg.reconnect_max_backoff_ms),
+            reconnect_max_attempts: file_cfg.reconnect_max_attempts,
+            request_timeout: (file_cfg.request_timeout_ms > 0)
+                .then(|| Duration::from_millis(file_cfg.request_timeout_ms)),
+            heartbeat_interval: (file_cfg.heartbeat_interval_secs > 0)
+                .then(|| Duration::from_secs(file_cfg.heartbeat_interval_secs)),
+            cancel_on_disconnect: file_cfg.cancel_on_disconnect,
+            cancel_on_disconnect_scope: file_cfg.cancel_on_disconnect_scope,
+            output_cancel_on_disconnect_csv: file_cfg.output_cancel_on_disconnect_csv,
+            concurrent_chains: file_cfg.concurrent_chains,
+            clock_sync_samples: file_cfg.clock_sync_samples,
+            clock_probe_interval: (file_cfg.clock_probe_interval_ms > 0)
+                .then(|| Duration::from_millis(file_cfg.clock_probe_interval_ms)),
+            output_clock_csv: file_cfg.output_clock_csv,
+            tick_to_trade: file_cfg.tick_to_trade,
+            tick_trigger: file_cfg.tick_trigger,
+            tick_trigger_every_n: file_cfg.tick_trigger_every_n,
+            tick_trigger_min_price_move: file_cfg.tick_trigger_min_price_move,
+            price_from_book: file_cfg.price_from_book,
+            feed_channels,
+            output_feed_csv: file_cfg.output_feed_csv,
+            resync_on_book_gap: file_cfg.resync_on_book_gap,
+            subscribe_user_channels: file_cfg.subscribe_user_channels,
+            output_user_csv: file_cfg.output_user_csv,
         })
     }
 }
+
+/// Resolve the `[[instruments]]` entries against the top-level order parameters; without
+/// entries the top-level instrument is the only one.
+fn resolve_instruments(file_cfg: &FileConfig) -> Result<Vec<InstrumentConfig>> {
+    let entries = if file_cfg.instruments.is_empty() {
+        vec![InstrumentFileConfig::named(&file_cfg.instrument_name)]
+    } else {
+        file_cfg.instruments.clone()
+    };
+
+    let mut instruments: Vec<InstrumentConfig> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let instrument = InstrumentConfig {
+            instrument_name: entry.instrument_name,
+            side: entry.side.unwrap_or(file_cfg.side),
+            order_amount: entry.order_amount.unwrap_or(file_cfg.order_amount),
+            base_price: entry.base_price.unwrap_or(file_cfg.base_price),
+            price_offset_percent: entry
+                .price_offset_percent
+                .unwrap_or(file_cfg.price_offset_percent),
+            edit_offset_step_percent: entry
+                .edit_offset_step_percent
+                .unwrap_or(file_cfg.edit_offset_step_percent),
+        };
+        if instrument.instrument_name.is_empty() {
+            anyhow::bail!("instrument_name must not be empty");
+        }
+        if instruments
+            .iter()
+            .any(|i| i.instrument_name == instrument.instrument_name)
+        {
+            anyhow::bail!(
+                "instrument '{}' is listed more than once",
+                instrument.instrument_name
+            );
+        }
+        if instrument.order_amount <= 0.0 || instrument.order_amount.is_nan() {
+            anyhow::bail!(
+                "order_amount must be positive (got {} for {})",
+                instrument.order_amount,
+                instrument.instrument_name
+            );
+        }
+        instruments.push(instrument);
+    }
+    Ok(instruments)
+}
+
+/// Public Deribit endpoint used when no explicit `endpoint_url` is configured.
+fn default_endpoint_url(testnet: bool) -> &'static str {
+    if testnet {
+        "wss://test.deribit.com/ws/api/v2"
+    } else {
+        "wss://www.deribit.com/ws/api/v2"
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_summary_quantiles() -> Vec<f64> {
+    DEFAULT_QUANTILES.to_vec()
+}
+
+fn default_reconnect_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_reconnect_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_request_timeout_ms() -> u64 {
+    5_000
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    30
+}
+
+fn default_concurrent_chains() -> usize {
+    1
+}
+
+fn default_clock_sync_samples() -> usize {
+    10
+}
+
+fn default_clock_probe_interval_ms() -> u64 {
+    1_000
+}
+
+fn default_output_clock_csv() -> String {
+    "output/clock_sync.csv".to_string()
+}
+
+fn default_output_feed_csv() -> String {
+    "output/feed_latency.csv".to_string()
+}
+
+fn default_output_user_csv() -> String {
+    "output/user_notifications.csv".to_string()
+}
+
+fn default_cancel_on_disconnect_scope() -> CancelOnDisconnectScope {
+    CancelOnDisconnectScope::Connection
+}
+
+fn default_output_cancel_on_disconnect_csv() -> String {
+    "output/cancel_on_disconnect.csv".to_string()
+}
+
+fn default_order_label() -> String {
+    "latency-tester".to_string()
+}
+
+fn default_instrument_mode() -> InstrumentMode {
+    InstrumentMode::RoundRobin
+}
+
+fn default_tick_trigger() -> TickTriggerKind {
+    TickTriggerKind::EveryNth
+}
+
+fn default_tick_trigger_every_n() -> usize {
+    1
+}
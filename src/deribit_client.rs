@@ -1,22 +1,161 @@
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use chrono::{DateTime, Utc};
+use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, RootCertStore};
 use serde_json::{json, Value};
 use tokio::net::TcpStream;
-use tokio::sync::{mpsc, oneshot, Mutex};
-use tokio_tungstenite::connect_async;
+use tokio::sync::{mpsc, oneshot, watch, Mutex};
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio::time::sleep;
+use tokio_rustls::TlsConnector;
 use tokio_tungstenite::tungstenite::client::IntoClientRequest;
 use tokio_tungstenite::tungstenite::protocol::Message;
-use tokio_tungstenite::MaybeTlsStream;
+use tokio_tungstenite::{client_async, connect_async_with_config, MaybeTlsStream, WebSocketStream};
+
+use crate::config::CancelOnDisconnectScope;
+use crate::order_book::BookUpdate;
+use crate::user_channels::UserUpdate;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+type PendingMap = HashMap<i64, oneshot::Sender<Result<RpcResponse, ClientError>>>;
+/// Monotonic and wall-clock time right after a frame was written (`None` if the write failed).
+type WrittenAck = oneshot::Sender<Option<(Instant, DateTime<Utc>)>>;
+
+/// WebSocket endpoint and TLS settings used to reach the Deribit API.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    /// `ws://` or `wss://` URL.
+    pub url: String,
+    /// Optional PEM file with extra CA roots (in addition to the native roots).
+    pub tls_ca_file: Option<String>,
+    /// Optional TLS server name (SNI) used instead of the URL host.
+    pub tls_server_name: Option<String>,
+}
+
+/// How the client reacts when the WebSocket connection is lost.
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    pub enabled: bool,
+    /// Delay before the first reconnect attempt; doubled after each failure.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Give up after this many consecutive failed attempts (0 = never give up).
+    pub max_attempts: u32,
+}
+
+/// Everything needed to (re)establish an authenticated session.
+#[derive(Debug, Clone)]
+pub struct ClientSettings {
+    pub endpoint: Endpoint,
+    pub client_id: String,
+    pub client_secret: String,
+    pub reconnect: ReconnectPolicy,
+    /// Maximum time to wait for each RPC response (`None` = wait forever).
+    pub request_timeout: Option<Duration>,
+    /// Interval requested via `public/set_heartbeat` (`None` = no heartbeats).
+    pub heartbeat_interval: Option<Duration>,
+    /// Enable `private/enable_cancel_on_disconnect` with this scope for every
+    /// (re-)established connection.
+    pub cancel_on_disconnect: Option<CancelOnDisconnectScope>,
+}
+
+/// Typed errors surfaced by [`DeribitClient`].
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    /// No response arrived within the configured request timeout.
+    #[error("request timed out after {0:?}")]
+    Timeout(Duration),
+    /// The connection dropped before the response arrived, or is currently being re-established.
+    #[error("connection lost")]
+    ConnectionLost,
+    /// The connection is closed for good (reconnect disabled or given up).
+    #[error("connection closed")]
+    ConnectionClosed,
+    /// Deribit answered with a JSON-RPC error object.
+    #[error("RPC error {code}: {message}")]
+    RpcError { code: i64, message: String },
+    #[error("serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Current state of the underlying WebSocket connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected,
+    Reconnecting,
+    /// Disconnected for good (reconnect disabled or given up).
+    Closed,
+}
+
+/// Kind of a connection lifecycle event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEventKind {
+    Disconnected,
+    ReconnectFailed,
+    Reconnected,
+    GaveUp,
+}
+
+impl ConnectionEventKind {
+    /// Name used as `op_type` in the latency CSV.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ConnectionEventKind::Disconnected => "disconnect",
+            ConnectionEventKind::ReconnectFailed => "reconnect_failed",
+            ConnectionEventKind::Reconnected => "reconnect",
+            ConnectionEventKind::GaveUp => "reconnect_gave_up",
+        }
+    }
+}
+
+/// Connection lifecycle event emitted by the reconnect supervisor.
+pub struct ConnectionEvent {
+    pub kind: ConnectionEventKind,
+    /// When the connection was lost (start of the gap).
+    pub disconnected_ts_mono: Instant,
+    pub disconnected_ts_wall: DateTime<Utc>,
+    /// When this event happened.
+    pub ts_mono: Instant,
+    pub ts_wall: DateTime<Utc>,
+    /// Reconnect attempt number (0 for the disconnect itself).
+    pub attempt: u32,
+    pub detail: String,
+}
+
+/// Answer to a Deribit `test_request` heartbeat (our `public/test` round trip).
+pub struct HeartbeatEvent {
+    pub sent: SendTimestamps,
+    pub result: Result<RpcResponse, ClientError>,
+}
+
+/// Events emitted by the client besides RPC responses and market data.
+pub enum ClientEvent {
+    Connection(ConnectionEvent),
+    Heartbeat(HeartbeatEvent),
+}
 
 /// Minimal market data event used by the latency logic.
 pub struct MarketDataEvent {
     pub recv_ts_mono: Instant,
+    pub recv_ts_wall: DateTime<Utc>,
     pub channel: String,
+    /// Exchange timestamp of the notification in milliseconds (for `trades.*`, the
+    /// newest trade's), if present.
+    pub exchange_ts_ms: Option<i64>,
+    /// Parsed payload of `book.*` channels (exchange timestamp, change ids, price levels).
+    pub book: Option<BookUpdate>,
+    /// Parsed payload of private `user.orders.*` / `user.trades.*` channels.
+    pub user: Option<UserUpdate>,
 }
 
 /// RPC response including timestamps when the message was received.
@@ -28,122 +167,213 @@ pub struct RpcResponse {
     pub recv_ts_wall: DateTime<Utc>,
 }
 
+impl RpcResponse {
+    /// The `result` payload, or the JSON-RPC `error` as [`ClientError::RpcError`].
+    pub fn result_or_error(&self) -> Result<&Value, ClientError> {
+        if let Some(err) = self.error.as_ref() {
+            return Err(ClientError::RpcError {
+                code: err.get("code").and_then(|v| v.as_i64()).unwrap_or(0),
+                message: err
+                    .get("message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("")
+                    .to_string(),
+            });
+        }
+        self.result.as_ref().ok_or_else(|| ClientError::RpcError {
+            code: 0,
+            message: "response has neither result nor error".to_string(),
+        })
+    }
+}
+
+/// Client-side timestamps of sending one request.
+#[derive(Debug, Clone, Copy)]
+pub struct SendTimestamps {
+    /// Before the request was serialized.
+    pub pre_serialize_mono: Instant,
+    /// After serialization, before the frame was queued for the writer task.
+    pub post_serialize_mono: Instant,
+    /// Right after the frame was written (and flushed) to the socket.
+    pub written_mono: Instant,
+    pub written_wall: DateTime<Utc>,
+}
+
+/// A request whose frame has been written to the socket.
+///
+/// Resolve it with [`PendingResponse::response`]; dropping it abandons the request.
+pub struct PendingResponse {
+    id: i64,
+    pub sent: SendTimestamps,
+    rx: oneshot::Receiver<Result<RpcResponse, ClientError>>,
+    inner: Arc<Inner>,
+}
+
+impl PendingResponse {
+    /// Wait for the response, at most the configured request timeout (counted from the write).
+    pub async fn response(self) -> Result<RpcResponse, ClientError> {
+        let outcome = match self.inner.settings.request_timeout {
+            Some(limit) => match tokio::time::timeout(limit, self.rx).await {
+                Ok(outcome) => outcome,
+                Err(_) => {
+                    // Drop the pending entry so a late response is discarded.
+                    self.inner.pending.lock().await.remove(&self.id);
+                    return Err(ClientError::Timeout(limit));
+                }
+            },
+            None => self.rx.await,
+        };
+
+        // A dropped sender means the pending map was cleared by the supervisor.
+        outcome.unwrap_or(Err(ClientError::ConnectionLost))
+    }
+}
+
+/// Text frame queued for the writer task.
+struct OutgoingFrame {
+    text: String,
+    written_tx: WrittenAck,
+}
+
+/// State shared between the client handles, the reader/writer tasks and the reconnect supervisor.
+struct Inner {
+    settings: ClientSettings,
+    /// Queue of the writer task owning the current connection's sink (`None` while disconnected).
+    writer_tx: Mutex<Option<mpsc::UnboundedSender<OutgoingFrame>>>,
+    pending: Mutex<PendingMap>,
+    next_id: AtomicI64,
+    /// Channels subscribed so far; restored after a reconnect.
+    subscriptions: Mutex<Vec<String>>,
+    /// Aborts the current connection's reader task, dropping the connection.
+    reader_abort: std::sync::Mutex<Option<AbortHandle>>,
+    md_tx: mpsc::UnboundedSender<MarketDataEvent>,
+    event_tx: mpsc::UnboundedSender<ClientEvent>,
+    state_tx: watch::Sender<ConnectionState>,
+}
+
+/// Cloneable handle to one Deribit connection; clones share the connection.
+#[derive(Clone)]
 pub struct DeribitClient {
-    pub(crate) ws_tx: futures::stream::SplitSink<
-        tokio_tungstenite::WebSocketStream<MaybeTlsStream<TcpStream>>,
-        Message,
-    >,
-    pending: Arc<Mutex<HashMap<i64, oneshot::Sender<RpcResponse>>>>,
-    next_id: Arc<Mutex<i64>>,
+    inner: Arc<Inner>,
+    state_rx: watch::Receiver<ConnectionState>,
 }
 
 impl DeribitClient {
+    /// Connect, authenticate, enable heartbeats and start the reconnect supervisor.
+    ///
+    /// Market data notifications are forwarded to `md_tx`; connection lifecycle
+    /// events (disconnects, reconnects) and heartbeat round trips to `event_tx`.
     pub async fn connect(
-        testnet: bool,
-        client_id: &str,
-        client_secret: &str,
+        settings: ClientSettings,
         md_tx: mpsc::UnboundedSender<MarketDataEvent>,
+        event_tx: mpsc::UnboundedSender<ClientEvent>,
     ) -> Result<Self> {
-        let url = if testnet {
-            "wss://test.deribit.com/ws/api/v2"
-        } else {
-            "wss://www.deribit.com/ws/api/v2"
-        };
-
-        let request = url.into_client_request()?;
-        let (ws_stream, _response) = connect_async(request).await?;
+        let ws_stream = open_stream(&settings.endpoint)
+            .await
+            .with_context(|| format!("failed to connect to '{}'", settings.endpoint.url))?;
         let (ws_tx, ws_rx) = ws_stream.split();
 
-        let pending: Arc<Mutex<HashMap<i64, oneshot::Sender<RpcResponse>>>> =
-            Arc::new(Mutex::new(HashMap::new()));
-        let pending_reader = pending.clone();
-        let next_id = Arc::new(Mutex::new(1_i64));
-
-        // Spawn reader task
-        tokio::spawn(async move {
-            let mut ws_rx = ws_rx;
-            while let Some(msg) = ws_rx.next().await {
-                match msg {
-                    Ok(Message::Text(txt)) => {
-                        let recv_ts_mono = Instant::now();
-                        let recv_ts_wall = Utc::now();
-                        if let Ok(raw) = serde_json::from_str::<Value>(&txt) {
-                            let id_opt = raw.get("id").and_then(|v| v.as_i64());
-                            if let Some(id) = id_opt {
-                                // RPC response
-                                let result = raw.get("result").cloned();
-                                let error = raw.get("error").cloned();
-                                let resp = RpcResponse {
-                                    result,
-                                    error,
-                                    raw,
-                                    recv_ts_mono,
-                                    recv_ts_wall,
-                                };
-                                let mut guard = pending_reader.lock().await;
-                                if let Some(tx) = guard.remove(&id) {
-                                    let _ = tx.send(resp);
-                                }
-                            } else if raw.get("method").and_then(|m| m.as_str())
-                                == Some("subscription")
-                            {
-                                // Subscription event
-                                if let Some(params) = raw.get("params") {
-                                    if let Some(channel) =
-                                        params.get("channel").and_then(|c| c.as_str())
-                                    {
-                                        let evt = MarketDataEvent {
-                                            recv_ts_mono,
-                                            channel: channel.to_string(),
-                                        };
-                                        let _ = md_tx.send(evt);
-                                    }
-                                }
-                            } else {
-                                // Other messages (heartbeat, etc.) can be ignored for now
-                            }
-                        }
-                    }
-                    Ok(Message::Ping(_)) => {
-                        // tungstenite will usually handle Pong automatically.
-                    }
-                    Ok(Message::Pong(_)) => {
-                        // Ignore.
-                    }
-                    Ok(Message::Binary(_)) => {
-                        // We don't use binary frames for Deribit JSON-RPC.
-                    }
-                    Ok(Message::Close(_)) => {
-                        // Remote closed the connection.
-                        break;
-                    }
-                    Ok(Message::Frame(_)) => {
-                        // Internal frame variant – can be ignored.
-                    }
-                    Err(_e) => {
-                        // Error on the WebSocket stream – stop the loop.
-                        break;
-                    }
-                }
-            }
+        let (state_tx, state_rx) = watch::channel(ConnectionState::Connected);
+        let inner = Arc::new(Inner {
+            settings,
+            writer_tx: Mutex::new(Some(spawn_writer(ws_tx))),
+            pending: Mutex::new(HashMap::new()),
+            next_id: AtomicI64::new(1),
+            subscriptions: Mutex::new(Vec::new()),
+            reader_abort: std::sync::Mutex::new(None),
+            md_tx,
+            event_tx,
+            state_tx,
         });
 
-        let mut client = Self {
-            ws_tx,
-            pending,
-            next_id,
-        };
+        let reader = spawn_reader(Arc::clone(&inner), ws_rx);
+
+        // Authenticate and enable heartbeats immediately
+        inner.establish_session().await?;
+
+        tokio::spawn(supervise(Arc::clone(&inner), reader));
+
+        Ok(Self { inner, state_rx })
+    }
+
+    /// Write a request and return as soon as its frame is on the socket.
+    ///
+    /// Responses are matched by id, so any number of requests can be in flight at once.
+    pub async fn send(&self, method: &str, params: Value) -> Result<PendingResponse, ClientError> {
+        self.inner.send(method, params).await
+    }
 
-        // Authenticate immediately
-        client
-            .authenticate(client_id, client_secret)
+    /// Send a request and wait for its response.
+    pub async fn send_rpc(&self, method: &str, params: Value) -> Result<RpcResponse, ClientError> {
+        self.inner.send_rpc(method, params).await
+    }
+
+    /// Subscribe to `channels` and remember them so they are restored after a reconnect.
+    pub async fn subscribe(&self, channels: &[String]) -> Result<RpcResponse, ClientError> {
+        let resp = self
+            .send_rpc(subscribe_method(channels), json!({ "channels": channels }))
+            .await?;
+        if resp.error.is_none() {
+            let mut guard = self.inner.subscriptions.lock().await;
+            for channel in channels {
+                if !guard.contains(channel) {
+                    guard.push(channel.clone());
+                }
+            }
+        }
+        Ok(resp)
+    }
+
+    /// Unsubscribe and subscribe public `channels` again, e.g. to get a fresh book snapshot.
+    /// They stay in the set restored after a reconnect.
+    pub async fn resubscribe(&self, channels: &[String]) -> Result<RpcResponse, ClientError> {
+        let resp = self
+            .send_rpc("public/unsubscribe", json!({ "channels": channels }))
+            .await?;
+        if resp.error.is_some() {
+            return Ok(resp);
+        }
+        self.send_rpc("public/subscribe", json!({ "channels": channels }))
             .await
-            .map_err(|e| anyhow!("authentication failed: {e}"))?;
+    }
 
-        Ok(client)
+    /// Drop the current connection without a close handshake, as a network failure would,
+    /// and wait until the client noticed: the next [`DeribitClient::wait_connected`] waits
+    /// for the reconnect (if enabled).
+    pub async fn drop_connection(&self) {
+        let reader = self.inner.reader_abort.lock().unwrap().take();
+        if let Some(reader) = reader {
+            reader.abort();
+        }
+        let mut state_rx = self.state_rx.clone();
+        let _ = state_rx
+            .wait_for(|s| *s != ConnectionState::Connected)
+            .await;
     }
 
-    async fn authenticate(&mut self, client_id: &str, client_secret: &str) -> Result<()> {
+    /// Wait until the connection is (re-)established.
+    ///
+    /// Fails with [`ClientError::ConnectionClosed`] if the connection is closed for good.
+    pub async fn wait_connected(&self) -> Result<(), ClientError> {
+        let mut state_rx = self.state_rx.clone();
+        let state = state_rx
+            .wait_for(|s| *s != ConnectionState::Reconnecting)
+            .await
+            .map_err(|_| ClientError::ConnectionClosed)?;
+        match *state {
+            ConnectionState::Connected => Ok(()),
+            _ => Err(ClientError::ConnectionClosed),
+        }
+    }
+}
+
+impl Inner {
+    async fn authenticate(self: &Arc<Self>) -> Result<()> {
+        let ClientSettings {
+            client_id,
+            client_secret,
+            ..
+        } = &self.settings;
         if client_id.is_empty() || client_secret.is_empty() {
             return Err(anyhow!(
                 "CLIENT_ID / CLIENT_SECRET are empty, cannot authenticate"
@@ -157,19 +387,66 @@ impl DeribitClient {
         });
 
         let resp = self.send_rpc("public/auth", params).await?;
-        if resp.error.is_some() {
-            return Err(anyhow!("auth error: {:?}", resp.error));
+        resp.result_or_error()?;
+        Ok(())
+    }
+
+    /// Authenticate, enable heartbeats and cancel-on-disconnect and (after a reconnect)
+    /// restore subscriptions.
+    async fn establish_session(self: &Arc<Self>) -> Result<()> {
+        self.authenticate()
+            .await
+            .map_err(|e| anyhow!("authentication failed: {e}"))?;
+
+        if let Some(interval) = self.settings.heartbeat_interval {
+            let resp = self
+                .send_rpc(
+                    "public/set_heartbeat",
+                    json!({ "interval": interval.as_secs() }),
+                )
+                .await?;
+            resp.result_or_error().context("set_heartbeat failed")?;
+        }
+
+        // A connection scope ends with the connection, so it is enabled again after every reconnect
+        if let Some(scope) = self.settings.cancel_on_disconnect {
+            let resp = self
+                .send_rpc(
+                    "private/enable_cancel_on_disconnect",
+                    json!({ "scope": scope.as_str() }),
+                )
+                .await?;
+            resp.result_or_error()
+                .context("enable_cancel_on_disconnect failed")?;
+        }
+
+        let channels = self.subscriptions.lock().await.clone();
+        if !channels.is_empty() {
+            let resp = self
+                .send_rpc(subscribe_method(&channels), json!({ "channels": channels }))
+                .await?;
+            resp.result_or_error().context("re-subscribe failed")?;
         }
         Ok(())
     }
 
-    pub async fn send_rpc(&mut self, method: &str, params: Value) -> Result<RpcResponse> {
-        let mut id_guard = self.next_id.lock().await;
-        let id = *id_guard;
-        *id_guard += 1;
-        drop(id_guard);
+    async fn send_rpc(
+        self: &Arc<Self>,
+        method: &str,
+        params: Value,
+    ) -> Result<RpcResponse, ClientError> {
+        self.send(method, params).await?.response().await
+    }
+
+    async fn send(
+        self: &Arc<Self>,
+        method: &str,
+        params: Value,
+    ) -> Result<PendingResponse, ClientError> {
+        let pre_serialize_mono = Instant::now();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
 
-        let (tx, rx) = oneshot::channel::<RpcResponse>();
+        let (tx, rx) = oneshot::channel::<Result<RpcResponse, ClientError>>();
 
         {
             let mut pending_guard = self.pending.lock().await;
@@ -182,10 +459,376 @@ impl DeribitClient {
             "method": method,
             "params": params,
         });
-        let txt = serde_json::to_string(&req)?;
-        self.ws_tx.send(Message::Text(txt)).await?;
+        let text = match serde_json::to_string(&req) {
+            Ok(text) => text,
+            Err(e) => {
+                self.pending.lock().await.remove(&id);
+                return Err(e.into());
+            }
+        };
 
-        let resp = rx.await?;
-        Ok(resp)
+        let post_serialize_mono = Instant::now();
+
+        let (written_tx, written_rx) = oneshot::channel();
+        let queued = match self.writer_tx.lock().await.as_ref() {
+            Some(writer_tx) => writer_tx.send(OutgoingFrame { text, written_tx }).is_ok(),
+            None => false,
+        };
+        let written = if queued {
+            written_rx.await.ok().flatten()
+        } else {
+            None
+        };
+        let Some((written_mono, written_wall)) = written else {
+            self.pending.lock().await.remove(&id);
+            return Err(self.disconnected_error());
+        };
+
+        Ok(PendingResponse {
+            id,
+            sent: SendTimestamps {
+                pre_serialize_mono,
+                post_serialize_mono,
+                written_mono,
+                written_wall,
+            },
+            rx,
+            inner: Arc::clone(self),
+        })
+    }
+
+    /// Error for a request that could not be written because there is no connection.
+    fn disconnected_error(&self) -> ClientError {
+        match *self.state_tx.borrow() {
+            ConnectionState::Closed => ClientError::ConnectionClosed,
+            _ => ClientError::ConnectionLost,
+        }
+    }
+
+    /// Fail every in-flight request with [`ClientError::ConnectionLost`].
+    async fn fail_pending(&self) {
+        let mut guard = self.pending.lock().await;
+        for (_, tx) in guard.drain() {
+            let _ = tx.send(Err(ClientError::ConnectionLost));
+        }
+    }
+
+    fn emit(
+        &self,
+        kind: ConnectionEventKind,
+        since: (Instant, DateTime<Utc>),
+        attempt: u32,
+        detail: String,
+    ) {
+        let _ = self.event_tx.send(ClientEvent::Connection(ConnectionEvent {
+            kind,
+            disconnected_ts_mono: since.0,
+            disconnected_ts_wall: since.1,
+            ts_mono: Instant::now(),
+            ts_wall: Utc::now(),
+            attempt,
+            detail,
+        }));
+    }
+
+    /// Answer a `test_request` heartbeat with `public/test` and report its round trip.
+    ///
+    /// Nothing is reported if the request could not be written at all.
+    async fn answer_test_request(self: &Arc<Self>) {
+        let Ok(pending) = self.send("public/test", json!({})).await else {
+            return;
+        };
+        let sent = pending.sent;
+        let result = pending.response().await;
+        let _ = self
+            .event_tx
+            .send(ClientEvent::Heartbeat(HeartbeatEvent { sent, result }));
+    }
+}
+
+/// Watch the current reader task and re-establish the session whenever it ends.
+async fn supervise(inner: Arc<Inner>, mut reader: JoinHandle<()>) {
+    let policy = inner.settings.reconnect.clone();
+
+    loop {
+        let _ = (&mut reader).await;
+
+        let since = (Instant::now(), Utc::now());
+        *inner.writer_tx.lock().await = None;
+        inner.fail_pending().await;
+        inner.emit(
+            ConnectionEventKind::Disconnected,
+            since,
+            0,
+            "WebSocket connection closed".to_string(),
+        );
+
+        if !policy.enabled {
+            let _ = inner.state_tx.send(ConnectionState::Closed);
+            return;
+        }
+        let _ = inner.state_tx.send(ConnectionState::Reconnecting);
+
+        let mut backoff = policy.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            if policy.max_attempts > 0 && attempt > policy.max_attempts {
+                inner.emit(
+                    ConnectionEventKind::GaveUp,
+                    since,
+                    attempt - 1,
+                    format!("giving up after {} attempts", policy.max_attempts),
+                );
+                let _ = inner.state_tx.send(ConnectionState::Closed);
+                return;
+            }
+
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(policy.max_backoff);
+
+            let ws_stream = match open_stream(&inner.settings.endpoint).await {
+                Ok(ws_stream) => ws_stream,
+                Err(e) => {
+                    inner.emit(
+                        ConnectionEventKind::ReconnectFailed,
+                        since,
+                        attempt,
+                        e.to_string(),
+                    );
+                    continue;
+                }
+            };
+
+            let (ws_tx, ws_rx) = ws_stream.split();
+            *inner.writer_tx.lock().await = Some(spawn_writer(ws_tx));
+            reader = spawn_reader(Arc::clone(&inner), ws_rx);
+
+            match inner.establish_session().await {
+                Ok(()) => {
+                    let _ = inner.state_tx.send(ConnectionState::Connected);
+                    inner.emit(
+                        ConnectionEventKind::Reconnected,
+                        since,
+                        attempt,
+                        "session restored".to_string(),
+                    );
+                    break;
+                }
+                Err(e) => {
+                    reader.abort();
+                    *inner.writer_tx.lock().await = None;
+                    inner.fail_pending().await;
+                    inner.emit(
+                        ConnectionEventKind::ReconnectFailed,
+                        since,
+                        attempt,
+                        e.to_string(),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Spawn the task writing queued frames to one WebSocket connection.
+///
+/// Each frame is acknowledged with the time right after it was written; the task ends
+/// on the first write error or once the returned sender is dropped.
+fn spawn_writer(mut ws_tx: WsSink) -> mpsc::UnboundedSender<OutgoingFrame> {
+    let (writer_tx, mut writer_rx) = mpsc::unbounded_channel::<OutgoingFrame>();
+    tokio::spawn(async move {
+        while let Some(frame) = writer_rx.recv().await {
+            let ok = ws_tx.send(Message::Text(frame.text)).await.is_ok();
+            let written = ok.then(|| (Instant::now(), Utc::now()));
+            let _ = frame.written_tx.send(written);
+            if !ok {
+                break;
+            }
+        }
+    });
+    writer_tx
+}
+
+/// Spawn the task reading one WebSocket connection until it closes (or is aborted by
+/// [`DeribitClient::drop_connection`]).
+fn spawn_reader(inner: Arc<Inner>, ws_rx: SplitStream<WsStream>) -> JoinHandle<()> {
+    let owner = Arc::clone(&inner);
+    let handle = tokio::spawn(async move {
+        let mut ws_rx = ws_rx;
+        while let Some(msg) = ws_rx.next().await {
+            match msg {
+                Ok(Message::Text(txt)) => {
+                    let recv_ts_mono = Instant::now();
+                    let recv_ts_wall = Utc::now();
+                    if let Ok(raw) = serde_json::from_str::<Value>(&txt) {
+                        let id_opt = raw.get("id").and_then(|v| v.as_i64());
+                        if let Some(id) = id_opt {
+                            // RPC response
+                            let result = raw.get("result").cloned();
+                            let error = raw.get("error").cloned();
+                            let resp = RpcResponse {
+                                result,
+                                error,
+                                raw,
+                                recv_ts_mono,
+                                recv_ts_wall,
+                            };
+                            let mut guard = inner.pending.lock().await;
+                            if let Some(tx) = guard.remove(&id) {
+                                let _ = tx.send(Ok(resp));
+                            }
+                        } else if raw.get("method").and_then(|m| m.as_str()) == Some("subscription")
+                        {
+                            // Subscription event
+                            if let Some(params) = raw.get("params") {
+                                if let Some(channel) =
+                                    params.get("channel").and_then(|c| c.as_str())
+                                {
+                                    let data = params.get("data");
+                                    let book = match data {
+                                        Some(data) if channel.starts_with("book.") => {
+                                            BookUpdate::from_data(data)
+                                        }
+                                        _ => None,
+                                    };
+                                    let user = match data {
+                                        Some(data) if channel.starts_with("user.") => {
+                                            UserUpdate::from_data(channel, data)
+                                        }
+                                        _ => None,
+                                    };
+                                    let evt = MarketDataEvent {
+                                        recv_ts_mono,
+                                        recv_ts_wall,
+                                        channel: channel.to_string(),
+                                        exchange_ts_ms: data.and_then(exchange_timestamp_ms),
+                                        book,
+                                        user,
+                                    };
+                                    let _ = inner.md_tx.send(evt);
+                                }
+                            }
+                        } else if raw.get("method").and_then(|m| m.as_str()) == Some("heartbeat") {
+                            // Deribit closes the connection if a test_request goes unanswered.
+                            // Answer from a separate task: the response arrives via this reader.
+                            let kind = raw
+                                .get("params")
+                                .and_then(|p| p.get("type"))
+                                .and_then(|t| t.as_str());
+                            if kind == Some("test_request") {
+                                let inner = Arc::clone(&inner);
+                                tokio::spawn(async move { inner.answer_test_request().await });
+                            }
+                        } else {
+                            // Other messages can be ignored for now
+                        }
+                    }
+                }
+                Ok(Message::Ping(_)) => {
+                    // tungstenite will usually handle Pong automatically.
+                }
+                Ok(Message::Pong(_)) => {
+                    // Ignore.
+                }
+                Ok(Message::Binary(_)) => {
+                    // We don't use binary frames for Deribit JSON-RPC.
+                }
+                Ok(Message::Close(_)) => {
+                    // Remote closed the connection.
+                    break;
+                }
+                Ok(Message::Frame(_)) => {
+                    // Internal frame variant – can be ignored.
+                }
+                Err(_e) => {
+                    // Error on the WebSocket stream – stop the loop.
+                    break;
+                }
+            }
+        }
+    });
+    *owner.reader_abort.lock().unwrap() = Some(handle.abort_handle());
+    handle
+}
+
+/// Private `user.*` channels need `private/subscribe` (which also accepts public channels).
+fn subscribe_method(channels: &[String]) -> &'static str {
+    if channels.iter().any(|c| c.starts_with("user.")) {
+        "private/subscribe"
+    } else {
+        "public/subscribe"
+    }
+}
+
+/// Exchange timestamp of a notification payload: its `timestamp`, or the newest
+/// `timestamp` of a list of trades.
+fn exchange_timestamp_ms(data: &Value) -> Option<i64> {
+    match data.as_array() {
+        Some(items) => items
+            .iter()
+            .filter_map(|item| item.get("timestamp")?.as_i64())
+            .max(),
+        None => data.get("timestamp")?.as_i64(),
+    }
+}
+
+/// Open the WebSocket connection described by `endpoint`.
+///
+/// Plain `ws://` URLs and `wss://` URLs without custom TLS settings go through
+/// `connect_async_with_config`. Otherwise the TLS handshake is done here so that extra CA
+/// roots and an SNI override can be applied.
+async fn open_stream(endpoint: &Endpoint) -> Result<WsStream> {
+    let request = endpoint.url.as_str().into_client_request()?;
+
+    let custom_tls = endpoint.tls_ca_file.is_some() || endpoint.tls_server_name.is_some();
+    if !custom_tls || request.uri().scheme_str() != Some("wss") {
+        // Disable Nagle's algorithm: small RPC frames must not be delayed
+        let (ws_stream, _response) = connect_async_with_config(request, None, true).await?;
+        return Ok(ws_stream);
     }
+
+    let host = request
+        .uri()
+        .host()
+        .ok_or_else(|| anyhow!("endpoint URL has no host"))?
+        .to_string();
+    let port = request.uri().port_u16().unwrap_or(443);
+
+    let sni = endpoint
+        .tls_server_name
+        .clone()
+        .unwrap_or_else(|| host.clone());
+    let server_name = ServerName::try_from(sni.clone())
+        .map_err(|e| anyhow!("invalid TLS server name '{}': {e}", sni))?;
+
+    let tls_config = build_tls_config(endpoint.tls_ca_file.as_deref())?;
+    let tcp = TcpStream::connect((host.as_str(), port)).await?;
+    tcp.set_nodelay(true)?;
+    let tls = TlsConnector::from(Arc::new(tls_config))
+        .connect(server_name, tcp)
+        .await?;
+
+    let (ws_stream, _response) = client_async(request, MaybeTlsStream::Rustls(tls)).await?;
+    Ok(ws_stream)
+}
+
+/// Build a rustls client config trusting the native roots plus an optional PEM bundle.
+fn build_tls_config(ca_file: Option<&str>) -> Result<ClientConfig> {
+    let mut roots = RootCertStore::empty();
+    roots.add_parsable_certificates(rustls_native_certs::load_native_certs()?);
+
+    if let Some(path) = ca_file {
+        let file =
+            File::open(path).with_context(|| format!("failed to open CA file at '{}'", path))?;
+        let mut reader = BufReader::new(file);
+        for cert in rustls_pemfile::certs(&mut reader) {
+            let cert = cert.with_context(|| format!("failed to parse CA file at '{}'", path))?;
+            roots.add(cert)?;
+        }
+    }
+
+    Ok(ClientConfig::builder()
+        .with_root_certificates(roots)
+        .with_no_client_auth())
 }
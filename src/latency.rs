@@ -7,7 +7,30 @@ use chrono::{DateTime, Utc};
 use csv::Writer;
 use serde::Serialize;
 
-use crate::deribit_client::RpcResponse;
+use crate::clock_sync::{ClockOffsetEstimator, OffsetEstimate, OffsetSample};
+use crate::deribit_client::{
+    ClientError, ConnectionEvent, HeartbeatEvent, MarketDataEvent, RpcResponse, SendTimestamps,
+};
+use crate::user_channels::UserNotificationTracker;
+
+/// `op_type` of the `public/test` round trips answering Deribit heartbeats.
+pub const HEARTBEAT_OP_TYPE: &str = "heartbeat";
+
+/// Requests whose ack changes exactly one order (and so triggers one `user.orders` notification).
+const ORDER_CHANGING_METHODS: [&str; 4] = [
+    "private/buy",
+    "private/sell",
+    "private/edit",
+    "private/cancel",
+];
+
+/// Requests cancelling several orders at once; their ack carries the number cancelled.
+const MASS_CANCEL_METHODS: [&str; 4] = [
+    "private/cancel_all",
+    "private/cancel_all_by_instrument",
+    "private/cancel_all_by_currency",
+    "private/cancel_by_label",
+];
 
 /// One latency sample for a single RPC request/response.
 #[derive(Debug, Serialize)]
@@ -16,8 +39,15 @@ pub struct RoundtripSample {
     pub rpc_method: String,
     pub instrument_name: String,
     pub order_id: Option<String>,
+    /// `label` sent with the request (orders placed by the tester, `cancel_by_label`).
+    pub label: Option<String>,
 
     pub tick_ts_mono_ns: Option<i64>,
+    /// Before the request was serialized.
+    pub pre_serialize_ts_mono_ns: Option<i64>,
+    /// After serialization, before the frame was queued for writing.
+    pub post_serialize_ts_mono_ns: Option<i64>,
+    /// Right after the frame was written to the socket.
     pub send_ts_mono_ns: i64,
     pub recv_ts_mono_ns: i64,
 
@@ -27,6 +57,10 @@ pub struct RoundtripSample {
     pub rtt_mono_us: i64,
     pub rtt_wall_us: i64,
 
+    /// Client overhead before the RTT starts: serialization, then queueing + socket write.
+    pub serialize_us: Option<i64>,
+    pub write_us: Option<i64>,
+
     pub tick_to_send_us: Option<i64>,
     pub tick_to_ack_us: Option<i64>,
 
@@ -34,9 +68,22 @@ pub struct RoundtripSample {
     pub engine_us_out: Option<i64>,
     pub engine_us_diff: Option<i64>,
 
+    /// Clock offset estimate (Deribit − local) used for the one-way estimates, and its uncertainty (±).
+    pub clock_offset_us: Option<i64>,
+    pub clock_offset_uncertainty_us: Option<i64>,
+    /// Estimated one-way latencies: Send → usIn and usOut → Ack.
+    pub est_outbound_us: Option<i64>,
+    pub est_inbound_us: Option<i64>,
+
+    /// Number of orders cancelled by a mass cancel (`private/cancel_all*`, `private/cancel_by_label`).
+    pub cancelled_count: Option<i64>,
+
     pub error_code: Option<i64>,
     pub error_msg: Option<String>,
 
+    /// No response arrived within the request timeout; `recv_*` is when the wait was abandoned.
+    pub timed_out: bool,
+
     /// Time between this Ack and the previous Ack (monotonic), in microseconds.
     pub ack_delta_prev_us: Option<i64>,
 }
@@ -46,6 +93,10 @@ pub struct LatencyLogger {
     writer: Writer<File>,
     program_start: Instant,
     last_ack_recv_ns: Option<i64>,
+    /// Fed by every response carrying `usIn` / `usOut`, plus `public/get_time` probes.
+    clock: ClockOffsetEstimator,
+    /// Pairs order acks with user channel notifications (`subscribe_user_channels`).
+    user_notifications: Option<UserNotificationTracker>,
 }
 
 /// Context for logging a single latency sample.
@@ -54,29 +105,79 @@ pub struct SampleContext<'a> {
     pub rpc_method: &'a str,
     pub instrument_name: &'a str,
     pub order_id: Option<&'a str>,
+    pub label: Option<&'a str>,
     pub tick_ts_mono_ns: Option<i64>,
-    pub send_ts_mono: Instant,
-    pub send_ts_wall: DateTime<Utc>,
-    pub resp: &'a RpcResponse,
+    pub sent: SendTimestamps,
+    pub outcome: SampleOutcome<'a>,
 }
 
-impl LatencyLogger {
-    pub fn new(csv_path: &str, program_start: Instant) -> Result<Self> {
-        if let Some(parent) = Path::new(csv_path).parent() {
-            if !parent.as_os_str().is_empty() {
-                create_dir_all(parent)?;
-            }
+/// How a single RPC request ended.
+pub enum SampleOutcome<'a> {
+    /// A response (result or error) was received.
+    Response(&'a RpcResponse),
+    /// No response within the request timeout.
+    Timeout {
+        ts_mono: Instant,
+        ts_wall: DateTime<Utc>,
+    },
+}
+
+/// Create a CSV writer at `csv_path`, creating missing parent directories.
+pub fn create_csv_writer(csv_path: &str) -> Result<Writer<File>> {
+    if let Some(parent) = Path::new(csv_path).parent() {
+        if !parent.as_os_str().is_empty() {
+            create_dir_all(parent)?;
         }
+    }
 
-        let file = File::create(csv_path)?;
-        let writer = Writer::from_writer(file);
+    let file = File::create(csv_path)?;
+    Ok(Writer::from_writer(file))
+}
+
+impl LatencyLogger {
+    pub fn new(csv_path: &str, program_start: Instant) -> Result<Self> {
+        let writer = create_csv_writer(csv_path)?;
         Ok(Self {
             writer,
             program_start,
             last_ack_recv_ns: None,
+            clock: ClockOffsetEstimator::default(),
+            user_notifications: None,
         })
     }
 
+    /// Pair the acks of order-changing requests with user channel notifications from now on.
+    pub fn track_user_notifications(&mut self, tracker: UserNotificationTracker) {
+        self.user_notifications = Some(tracker);
+    }
+
+    /// Record a `user.orders` / `user.trades` notification (ignored unless tracking).
+    pub fn log_user_notification(&mut self, evt: &MarketDataEvent) -> Result<()> {
+        let (Some(tracker), Some(update)) = (self.user_notifications.as_mut(), evt.user.as_ref())
+        else {
+            return Ok(());
+        };
+        tracker.record_notification(
+            &evt.channel,
+            update,
+            evt.recv_ts_mono,
+            evt.recv_ts_wall,
+            self.clock.estimate(),
+        )
+    }
+
+    /// Current clock offset estimate (Deribit − local).
+    pub fn clock_estimate(&self) -> Option<OffsetEstimate> {
+        self.clock.estimate()
+    }
+
+    /// Feed an offset sample that is not logged as a latency sample (e.g. from
+    /// `public/get_time` probes) to the clock offset estimator; returns the new estimate.
+    pub fn record_clock_sample(&mut self, sample: OffsetSample) -> Option<OffsetEstimate> {
+        self.clock.add(sample);
+        self.clock.estimate()
+    }
+
     fn instant_to_ns_since_start(&self, t: Instant) -> i64 {
         let dur = t.duration_since(self.program_start);
         dur.as_nanos() as i64
@@ -136,14 +237,18 @@ impl LatencyLogger {
             rpc_method,
             instrument_name,
             order_id,
+            label,
             tick_ts_mono_ns,
-            send_ts_mono,
-            send_ts_wall,
-            resp,
+            sent,
+            outcome,
         } = ctx;
+        let send_ts_mono = sent.written_mono;
+        let send_ts_wall = sent.written_wall;
 
-        let recv_ts_mono = resp.recv_ts_mono;
-        let recv_ts_wall = resp.recv_ts_wall;
+        let (recv_ts_mono, recv_ts_wall, resp) = match outcome {
+            SampleOutcome::Response(resp) => (resp.recv_ts_mono, resp.recv_ts_wall, Some(resp)),
+            SampleOutcome::Timeout { ts_mono, ts_wall } => (ts_mono, ts_wall, None),
+        };
 
         let send_mono_ns = self.instant_to_ns_since_start(send_ts_mono);
         let recv_mono_ns = self.instant_to_ns_since_start(recv_ts_mono);
@@ -161,36 +266,108 @@ impl LatencyLogger {
             ((ack_ns - tick_ns) as f64 / 1000.0).round() as i64
         });
 
-        let (engine_us_in, engine_us_out, engine_us_diff) = Self::extract_engine_timestamps(resp);
-        let (error_code, error_msg) = Self::extract_error(resp);
+        let (engine_us_in, engine_us_out, engine_us_diff) = resp
+            .map(Self::extract_engine_timestamps)
+            .unwrap_or((None, None, None));
+        let (error_code, error_msg) = resp.map(Self::extract_error).unwrap_or((None, None));
+        let cancelled_count = resp
+            .filter(|_| MASS_CANCEL_METHODS.contains(&rpc_method))
+            .and_then(|resp| resp.result.as_ref()?.as_i64());
 
-        let ack_delta_prev_us = if let Some(last_ns) = self.last_ack_recv_ns {
-            let delta_ns = recv_mono_ns - last_ns;
-            Some(((delta_ns as f64) / 1000.0).round() as i64)
-        } else {
-            None
+        // One-way estimates use the offset known before this response, which is then
+        // fed to the estimator itself
+        let (clock_offset, est_outbound_us, est_inbound_us) =
+            match (self.clock.estimate(), engine_us_in, engine_us_out) {
+                (Some(est), Some(us_in), Some(us_out)) => (
+                    Some(est),
+                    Some(us_in - (send_ts_wall.timestamp_micros() + est.offset_us)),
+                    Some(recv_ts_wall.timestamp_micros() + est.offset_us - us_out),
+                ),
+                _ => (None, None, None),
+            };
+        if let (Some(us_in), Some(us_out)) = (engine_us_in, engine_us_out) {
+            self.clock.add(OffsetSample::from_exchange(
+                send_ts_wall,
+                us_in,
+                us_out,
+                recv_ts_wall,
+            ));
+        }
+
+        // Timeouts and heartbeats are not order Acks: they neither have nor reset an Ack interval
+        let is_ack = resp.is_some() && op_type != HEARTBEAT_OP_TYPE;
+        let ack_delta_prev_us = match (is_ack, self.last_ack_recv_ns) {
+            (true, Some(last_ns)) => {
+                let delta_ns = recv_mono_ns - last_ns;
+                Some(((delta_ns as f64) / 1000.0).round() as i64)
+            }
+            _ => None,
         };
-        self.last_ack_recv_ns = Some(recv_mono_ns);
+        if is_ack {
+            self.last_ack_recv_ns = Some(recv_mono_ns);
+        }
+
+        // A request on an order without a clean ack may or may not have changed it
+        if let (Some(tracker), Some(order_id)) = (self.user_notifications.as_mut(), order_id) {
+            if ORDER_CHANGING_METHODS.contains(&rpc_method)
+                && (resp.is_none() || error_code.is_some())
+            {
+                tracker.record_failed_request(order_id, recv_ts_mono);
+            }
+        }
+        if let (Some(tracker), Some(resp)) = (self.user_notifications.as_mut(), resp) {
+            let changed_order_id = resp
+                .result
+                .as_ref()
+                .filter(|_| ORDER_CHANGING_METHODS.contains(&rpc_method))
+                .and_then(|result| result.get("order").unwrap_or(result).get("order_id"))
+                .and_then(|v| v.as_str());
+            if let Some(changed_order_id) = changed_order_id {
+                tracker.record_ack(
+                    changed_order_id,
+                    op_type,
+                    resp.recv_ts_mono,
+                    engine_us_out,
+                    self.clock.estimate(),
+                )?;
+            }
+        }
 
         let sample = RoundtripSample {
             op_type: op_type.to_string(),
             rpc_method: rpc_method.to_string(),
             instrument_name: instrument_name.to_string(),
             order_id: order_id.map(|s| s.to_string()),
+            label: label.map(|s| s.to_string()),
             tick_ts_mono_ns,
+            pre_serialize_ts_mono_ns: Some(self.instant_to_ns_since_start(sent.pre_serialize_mono)),
+            post_serialize_ts_mono_ns: Some(
+                self.instant_to_ns_since_start(sent.post_serialize_mono),
+            ),
             send_ts_mono_ns: send_mono_ns,
             recv_ts_mono_ns: recv_mono_ns,
             send_ts_wall_iso: self.wall_to_iso(send_ts_wall),
             recv_ts_wall_iso: self.wall_to_iso(recv_ts_wall),
             rtt_mono_us,
             rtt_wall_us,
+            serialize_us: Some(Self::duration_us(
+                sent.pre_serialize_mono,
+                sent.post_serialize_mono,
+            )),
+            write_us: Some(Self::duration_us(sent.post_serialize_mono, send_ts_mono)),
             tick_to_send_us,
             tick_to_ack_us,
             engine_us_in,
             engine_us_out,
             engine_us_diff,
+            clock_offset_us: clock_offset.map(|est| est.offset_us),
+            clock_offset_uncertainty_us: clock_offset.map(|est| est.uncertainty_us),
+            est_outbound_us,
+            est_inbound_us,
+            cancelled_count,
             error_code,
             error_msg,
+            timed_out: resp.is_none(),
             ack_delta_prev_us,
         };
 
@@ -198,4 +375,156 @@ impl LatencyLogger {
         self.writer.flush()?;
         Ok(())
     }
+
+    /// Log the `public/test` round trip answering a heartbeat as one CSV row.
+    ///
+    /// Failures other than timeouts (e.g. connection lost) are not latency samples and are skipped.
+    pub fn log_heartbeat(&mut self, evt: &HeartbeatEvent) -> Result<()> {
+        let outcome = match &evt.result {
+            Ok(resp) => SampleOutcome::Response(resp),
+            Err(ClientError::Timeout(_)) => SampleOutcome::Timeout {
+                ts_mono: Instant::now(),
+                ts_wall: Utc::now(),
+            },
+            Err(_) => return Ok(()),
+        };
+
+        self.log_sample(SampleContext {
+            op_type: HEARTBEAT_OP_TYPE,
+            rpc_method: "public/test",
+            instrument_name: "",
+            order_id: None,
+            label: None,
+            tick_ts_mono_ns: None,
+            sent: evt.sent,
+            outcome,
+        })
+    }
+
+    /// Log a connection lifecycle event (disconnect / reconnect) as one CSV row.
+    ///
+    /// The send/recv columns span the gap from the disconnect to this event, so
+    /// `rtt_mono_us` is the time spent without a connection so far.
+    pub fn log_connection_event(&mut self, evt: &ConnectionEvent) -> Result<()> {
+        let sample = RoundtripSample {
+            op_type: evt.kind.as_str().to_string(),
+            rpc_method: String::new(),
+            instrument_name: String::new(),
+            order_id: None,
+            label: None,
+            tick_ts_mono_ns: None,
+            pre_serialize_ts_mono_ns: None,
+            post_serialize_ts_mono_ns: None,
+            send_ts_mono_ns: self.instant_to_ns_since_start(evt.disconnected_ts_mono),
+            recv_ts_mono_ns: self.instant_to_ns_since_start(evt.ts_mono),
+            send_ts_wall_iso: self.wall_to_iso(evt.disconnected_ts_wall),
+            recv_ts_wall_iso: self.wall_to_iso(evt.ts_wall),
+            rtt_mono_us: Self::duration_us(evt.disconnected_ts_mono, evt.ts_mono),
+            rtt_wall_us: Self::duration_wall_us(evt.disconnected_ts_wall, evt.ts_wall),
+            serialize_us: None,
+            write_us: None,
+            tick_to_send_us: None,
+            tick_to_ack_us: None,
+            engine_us_in: None,
+            engine_us_out: None,
+            engine_us_diff: None,
+            clock_offset_us: None,
+            clock_offset_uncertainty_us: None,
+            est_outbound_us: None,
+            est_inbound_us: None,
+            cancelled_count: None,
+            error_code: None,
+            error_msg: Some(format!("attempt {}: {}", evt.attempt, evt.detail)),
+            timed_out: false,
+            ack_delta_prev_us: None,
+        };
+
+        self.writer.serialize(sample)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Exchange-to-local latency of one market data notification.
+#[derive(Debug, Serialize)]
+pub struct FeedSample {
+    pub channel: String,
+    pub recv_ts_mono_ns: i64,
+    pub recv_ts_wall_iso: String,
+
+    /// Exchange timestamp of the notification (newest trade for `trades.*`), milliseconds.
+    pub exchange_ts_ms: i64,
+
+    /// Book sequence of `book.*` notifications; `sequence_gap` marks a raw book update whose
+    /// `prev_change_id` did not continue the local book (updates were lost).
+    pub change_id: Option<i64>,
+    pub prev_change_id: Option<i64>,
+    pub sequence_gap: bool,
+
+    /// Local receive wall time minus exchange timestamp, in microseconds.
+    pub feed_latency_us: i64,
+
+    /// Clock offset estimate (Deribit − local) at receive time and the feed latency
+    /// corrected by it.
+    pub clock_offset_us: Option<i64>,
+    pub clock_offset_uncertainty_us: Option<i64>,
+    pub feed_latency_corrected_us: Option<i64>,
+}
+
+/// Writes the feed latency of every timestamped market data notification to its own CSV.
+pub struct FeedLatencyLogger {
+    writer: Writer<File>,
+    program_start: Instant,
+}
+
+impl FeedLatencyLogger {
+    pub fn new(csv_path: &str, program_start: Instant) -> Result<Self> {
+        Ok(Self {
+            writer: create_csv_writer(csv_path)?,
+            program_start,
+        })
+    }
+
+    /// Log one notification; events without an exchange timestamp are skipped.
+    pub fn log(
+        &mut self,
+        evt: &MarketDataEvent,
+        sequence_gap: bool,
+        clock: Option<OffsetEstimate>,
+    ) -> Result<()> {
+        let Some(exchange_ts_ms) = evt.exchange_ts_ms else {
+            return Ok(());
+        };
+
+        // Deribit timestamps have millisecond resolution
+        let feed_latency_us = evt.recv_ts_wall.timestamp_micros() - exchange_ts_ms * 1000;
+        let sample = FeedSample {
+            channel: evt.channel.clone(),
+            recv_ts_mono_ns: evt
+                .recv_ts_mono
+                .duration_since(self.program_start)
+                .as_nanos() as i64,
+            recv_ts_wall_iso: evt.recv_ts_wall.to_rfc3339(),
+            exchange_ts_ms,
+            change_id: evt.book.as_ref().map(|b| b.change_id),
+            prev_change_id: evt.book.as_ref().and_then(|b| b.prev_change_id),
+            sequence_gap,
+            feed_latency_us,
+            clock_offset_us: clock.map(|est| est.offset_us),
+            clock_offset_uncertainty_us: clock.map(|est| est.uncertainty_us),
+            feed_latency_corrected_us: clock.map(|est| feed_latency_us + est.offset_us),
+        };
+
+        self.writer.serialize(sample)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Whether a CSV row with this `op_type` is a connection event rather than an RPC sample.
+pub fn is_connection_event(op_type: &str) -> bool {
+    matches!(
+        op_type,
+        "disconnect" | "reconnect" | "reconnect_failed" | "reconnect_gave_up"
+    )
 }
@@ -1,33 +1,125 @@
+mod cancel_on_disconnect;
+mod cli;
+mod clock_sync;
 mod config;
 mod deribit_client;
 mod latency;
+mod market_data;
+mod order_book;
+mod scenario;
 mod summary;
+mod tick_trigger;
+mod user_channels;
 
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
 use chrono::Utc;
+use clap::Parser;
 use serde_json::json;
-use tokio::sync::{mpsc, Mutex, RwLock};
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
 use tokio::time::sleep;
 
-use crate::config::{Config, OrderSide};
-use crate::deribit_client::{DeribitClient, MarketDataEvent, RpcResponse};
-use crate::latency::{LatencyLogger, SampleContext};
+use crate::cancel_on_disconnect::check_order_cancelled;
+use crate::cli::{Cli, Command, RunArgs};
+use crate::clock_sync::ClockProbe;
+use crate::config::{Config, FileConfig, InstrumentConfig, InstrumentMode, OrderSide};
+use crate::deribit_client::{
+    ClientError, ClientEvent, ClientSettings, DeribitClient, Endpoint, MarketDataEvent,
+    ReconnectPolicy, RpcResponse,
+};
+use crate::latency::{
+    create_csv_writer, FeedLatencyLogger, LatencyLogger, SampleContext, SampleOutcome,
+};
+use crate::market_data::{spawn_market_data_task, MarketState, TriggerSetup};
+use crate::scenario::{ScenarioStep, StepAction};
+use crate::tick_trigger::{TickTrigger, TriggerReceiver, TriggerTick};
+use crate::user_channels::{user_channels, UserNotificationTracker};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Load configuration from config.toml in the current working directory
-    let cfg = Config::load_from_file("config.toml")?;
+    let cli = Cli::parse();
 
+    match cli.command {
+        None => run(load_config(&RunArgs::with_default_config())?).await,
+        Some(Command::Run(args)) => run(load_config(&args)?).await,
+        Some(Command::Summarize {
+            csv,
+            clock_csv,
+            feed_csv,
+            user_csv,
+            cancel_on_disconnect_csv,
+            summary_args,
+        }) => {
+            let opts = summary_args.options()?;
+            summary::print_summary_from_csv(&csv, &opts)?;
+            if let Some(path) = clock_csv {
+                summary::print_clock_summary_from_csv(&path, &opts)?;
+            }
+            if let Some(path) = feed_csv {
+                summary::print_feed_summary_from_csv(&path, &opts)?;
+            }
+            if let Some(path) = user_csv {
+                summary::print_user_summary_from_csv(&path, &opts)?;
+            }
+            match cancel_on_disconnect_csv {
+                Some(path) => summary::print_cancel_on_disconnect_summary_from_csv(&path, &opts),
+                None => Ok(()),
+            }
+        }
+        Some(Command::Compare {
+            baseline,
+            candidate,
+            summary_args,
+        }) => summary::print_comparison_from_csv(&baseline, &candidate, &summary_args.options()?),
+        Some(Command::ValidateConfig(args)) => validate_config(&args),
+        Some(Command::CancelOnDisconnectTest(args)) => {
+            cancel_on_disconnect_test(load_config(&args)?).await
+        }
+    }
+}
+
+/// Load the config file named in `args` and apply its command-line overrides.
+fn load_config(args: &RunArgs) -> Result<Config> {
+    let mut file_cfg = FileConfig::load_from_file(&args.config)?;
+    args.apply_overrides(&mut file_cfg);
+    Config::from_file_config(file_cfg)
+}
+
+fn validate_config(args: &RunArgs) -> Result<()> {
+    let cfg = load_config(args)?;
+    println!("Config '{}' is valid.", args.config);
+    println!("    endpoint:       {}", cfg.endpoint_url);
+    for instrument in &cfg.instruments {
+        println!(
+            "    instrument:     {} ({}, side={:?}, order_amount={})",
+            instrument.instrument_name,
+            instrument.currency(),
+            instrument.side,
+            instrument.order_amount
+        );
+    }
+    if cfg.instruments.len() > 1 {
+        println!("    mode:           {:?}", cfg.instrument_mode);
+    }
+    println!("    iterations:     {} per instrument", cfg.num_iterations);
+    println!("    order label:    {}", cfg.run_label);
+    println!("    output CSV:     {}", cfg.output_latency_csv);
+    Ok(())
+}
+
+async fn run(cfg: Config) -> Result<()> {
     let program_start = Instant::now();
 
     println!(
-        "[{}] Starting Deribit latency tester (instrument={}, testnet={})",
+        "[{}] Starting Deribit latency tester (instruments={}, testnet={}, endpoint={})",
         Utc::now().to_rfc3339(),
-        cfg.instrument_name,
-        cfg.testnet
+        instrument_names(&cfg).join(","),
+        cfg.testnet,
+        cfg.endpoint_url
     );
     println!(
         "[{}] Latency samples will be written to {}",
@@ -35,50 +127,101 @@ async fn main() -> Result<()> {
         cfg.output_latency_csv
     );
 
-    // Channel for market data events (book.<instrument>.raw)
-    let (md_tx, mut md_rx) = mpsc::unbounded_channel::<MarketDataEvent>();
+    // Ctrl-C / SIGTERM stop the run gracefully (see `spawn_shutdown_listener`)
+    let (shutdown_tx, _) = watch::channel(false);
+    spawn_shutdown_listener(shutdown_tx.clone());
+
+    // Channel for market data events (book.<instrument>.raw, feed and user channels)
+    let (md_tx, md_rx) = mpsc::unbounded_channel::<MarketDataEvent>();
 
-    // Shared monotonic timestamp of latest market data tick (ns since program_start)
-    let last_tick_ns = Arc::new(RwLock::new(None::<i64>));
+    // Latest market data tick and local order books, shared with the chains
+    let market = Arc::new(RwLock::new(MarketState::default()));
+
+    // Channel for client events (disconnects / reconnects, heartbeat round trips)
+    let (client_event_tx, mut client_event_rx) = mpsc::unbounded_channel::<ClientEvent>();
 
     // Connect Deribit WebSocket client (this also authenticates)
-    let mut client =
-        DeribitClient::connect(cfg.testnet, &cfg.client_id, &cfg.client_secret, md_tx).await?;
+    let client = DeribitClient::connect(client_settings(&cfg), md_tx, client_event_tx).await?;
 
     println!("[{}] Connected and authenticated.", Utc::now().to_rfc3339());
 
-    // Spawn a task to keep track of latest MD tick timestamps
-    {
-        let last_tick_ns_clone = Arc::clone(&last_tick_ns);
-        let program_start_clone = program_start;
-        tokio::spawn(async move {
-            while let Some(evt) = md_rx.recv().await {
-                // Only consider book.<instrument>.raw events
-                if !evt.channel.starts_with("book.") {
-                    continue;
-                }
-                let mono_ns = evt
-                    .recv_ts_mono
-                    .duration_since(program_start_clone)
-                    .as_nanos() as i64;
-                let mut guard = last_tick_ns_clone.write().await;
-                *guard = Some(mono_ns);
-            }
-        });
+    // Prepare latency logger
+    let logger = Arc::new(Mutex::new(LatencyLogger::new(
+        &cfg.output_latency_csv,
+        program_start,
+    )?));
+    println!(
+        "[{}] Writing latency samples to {}",
+        Utc::now().to_rfc3339(),
+        cfg.output_latency_csv
+    );
+    if cfg.subscribe_user_channels {
+        let tracker = UserNotificationTracker::new(&cfg.output_user_csv, program_start)?;
+        logger.lock().await.track_user_notifications(tracker);
+        println!(
+            "[{}] Ack → user notification delays will be written to {}",
+            Utc::now().to_rfc3339(),
+            cfg.output_user_csv
+        );
+    }
+
+    // Ticks firing orders in tick-to-trade mode, per instrument
+    let mut triggers = Vec::new();
+    let mut trigger_rxs = HashMap::new();
+    if cfg.tick_to_trade {
+        for instrument in &cfg.instruments {
+            let (tx, rx) = watch::channel(None::<TriggerTick>);
+            triggers.push(TriggerSetup {
+                instrument: instrument.instrument_name.clone(),
+                trigger: TickTrigger::new(
+                    cfg.tick_trigger,
+                    cfg.tick_trigger_every_n,
+                    cfg.tick_trigger_min_price_move,
+                ),
+                tx,
+            });
+            trigger_rxs.insert(instrument.instrument_name.clone(), rx);
+        }
     }
 
-    // Subscribe to raw order book for real MD timestamps
+    // Spawn a task to keep track of latest MD tick timestamps, the local books and,
+    // in tick-to-trade mode, the ticks that fire orders
+    let feed_logger = FeedLatencyLogger::new(&cfg.output_feed_csv, program_start)?;
+    spawn_market_data_task(
+        md_rx,
+        Arc::clone(&market),
+        program_start,
+        feed_logger,
+        Arc::clone(&logger),
+        triggers,
+        cfg.resync_on_book_gap.then(|| client.clone()),
+    );
+
+    // Subscribe to raw order book for real MD timestamps, plus the feed latency channels
+    let mut channels = Vec::new();
     if cfg.subscribe_raw_book {
-        let channel = format!("book.{}.raw", cfg.instrument_name);
+        for name in instrument_names(&cfg) {
+            channels.push(format!("book.{name}.raw"));
+        }
+    }
+    channels.extend(cfg.feed_channels.iter().cloned());
+    if cfg.subscribe_user_channels {
+        for name in instrument_names(&cfg) {
+            channels.extend(user_channels(name));
+        }
+    }
+    if !channels.is_empty() {
         println!(
             "[{}] Subscribing to {} ...",
             Utc::now().to_rfc3339(),
-            channel
+            channels.join(", ")
         );
-        let params = json!({
-            "channels": [channel]
-        });
-        let resp = client.send_rpc("public/subscribe", params).await?;
+        println!(
+            "[{}] Market data feed latency will be written to {}",
+            Utc::now().to_rfc3339(),
+            cfg.output_feed_csv
+        );
+        let resp = client.subscribe(&channels).await?;
         if resp.error.is_some() {
             eprintln!("Subscribe error: {:?}", resp.error);
         } else {
@@ -86,83 +229,384 @@ async fn main() -> Result<()> {
         }
     }
 
-    // Optional: get instrument info (e.g. tick_size)
-    let tick_size = fetch_tick_size(&mut client, &cfg.instrument_name).await?;
+    let mut instruments = Vec::with_capacity(cfg.instruments.len());
+    for instrument_cfg in &cfg.instruments {
+        let name = &instrument_cfg.instrument_name;
+
+        // Optional: get instrument info (e.g. tick_size)
+        let tick_size = fetch_tick_size(&client, name).await?;
+        println!(
+            "[{}] Instrument {} tick_size={}",
+            Utc::now().to_rfc3339(),
+            name,
+            tick_size
+        );
+
+        // Optional: get a reference price (ticker)
+        let base_price = match fetch_ticker_price(&client, name).await {
+            Ok(p) => p,
+            Err(_) => instrument_cfg.base_price,
+        };
+        println!(
+            "[{}] Using base price ~{} for {} order placement",
+            Utc::now().to_rfc3339(),
+            base_price,
+            name
+        );
+
+        instruments.push(TradedInstrument {
+            cfg: instrument_cfg.clone(),
+            tick_size,
+            base_price,
+            tick_triggers: trigger_rxs.remove(name),
+        });
+    }
+
+    // Orders left over by earlier runs that died mid-iteration (opt-in: testers running
+    // with the same order_label right now would lose their orders too)
+    if cfg.cancel_stale_orders {
+        let label_prefix = format!("{}-", cfg.order_label);
+        if let Err(e) = cancel_labelled_orders(&client, &instruments, &label_prefix).await {
+            eprintln!("Failed to cancel leftover orders of earlier runs: {e}");
+        }
+    }
     println!(
-        "[{}] Instrument {} tick_size={}",
+        "[{}] Labelling orders with {}",
         Utc::now().to_rfc3339(),
-        cfg.instrument_name,
-        tick_size
+        cfg.run_label
     );
 
-    // Optional: get a reference price (ticker)
-    let base_price = match fetch_ticker_price(&mut client, &cfg.instrument_name).await {
-        Ok(p) => p,
-        Err(_) => cfg.base_price,
-    };
+    // Probe Deribit's clock before and during the run (offset, drift and the offset
+    // used for one-way latency estimates)
+    let clock_probe_enabled = cfg.clock_sync_samples > 0 || cfg.clock_probe_interval.is_some();
+    let mut clock_probe_task = None;
+    if clock_probe_enabled {
+        let mut probe = ClockProbe::new(
+            &cfg.output_clock_csv,
+            client.clone(),
+            Arc::clone(&logger),
+            program_start,
+        )?;
+        match probe.initial_burst(cfg.clock_sync_samples).await {
+            Ok(Some(est)) => println!(
+                "[{}] Clock offset to Deribit: {} µs (± {} µs)",
+                Utc::now().to_rfc3339(),
+                est.offset_us,
+                est.uncertainty_us
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!("Clock sync failed, one-way estimates start later: {e}"),
+        }
+        clock_probe_task = cfg
+            .clock_probe_interval
+            .map(|interval| probe.spawn_periodic(interval));
+    }
+
+    // Spawn a task to log client events into the latency CSV, so connection gaps
+    // and heartbeat round trips are visible
+    {
+        let logger_clone = Arc::clone(&logger);
+        tokio::spawn(async move {
+            while let Some(evt) = client_event_rx.recv().await {
+                let result = match evt {
+                    ClientEvent::Connection(evt) => {
+                        println!(
+                            "[{}] Connection event: {} (attempt {}): {}",
+                            evt.ts_wall.to_rfc3339(),
+                            evt.kind.as_str(),
+                            evt.attempt,
+                            evt.detail
+                        );
+                        logger_clone.lock().await.log_connection_event(&evt)
+                    }
+                    ClientEvent::Heartbeat(evt) => logger_clone.lock().await.log_heartbeat(&evt),
+                };
+                if let Err(e) = result {
+                    eprintln!("Failed to log client event: {e}");
+                }
+            }
+        });
+    }
+
+    if cfg.tick_to_trade {
+        println!(
+            "[{}] Tick-to-trade mode: firing open / edit on {:?} ticks",
+            Utc::now().to_rfc3339(),
+            cfg.tick_trigger
+        );
+    }
+
+    let outcome =
+        run_roundtrip_test(&client, &cfg, &instruments, &market, &logger, &shutdown_tx).await;
+
+    // Orders this run could not cancel itself (e.g. opens whose response never arrived)
+    if let Err(e) = cancel_labelled_orders(&client, &instruments, &cfg.run_label).await {
+        eprintln!("Failed to cancel leftover orders: {e}");
+    }
+
+    if let Some(task) = clock_probe_task {
+        task.abort();
+    }
+
+    // Also summarize interrupted or failed runs: every sample is already on disk
+    if cfg.print_summary {
+        if let Err(e) = summary::print_summary_from_csv(&cfg.output_latency_csv, &cfg.summary) {
+            eprintln!("Failed to print summary: {e}");
+        }
+        if clock_probe_enabled {
+            if let Err(e) =
+                summary::print_clock_summary_from_csv(&cfg.output_clock_csv, &cfg.summary)
+            {
+                eprintln!("Failed to print clock sync summary: {e}");
+            }
+        }
+        if cfg.subscribe_raw_book || !cfg.feed_channels.is_empty() {
+            if let Err(e) = summary::print_feed_summary_from_csv(&cfg.output_feed_csv, &cfg.summary)
+            {
+                eprintln!("Failed to print market data feed summary: {e}");
+            }
+        }
+        if cfg.subscribe_user_channels {
+            if let Err(e) = summary::print_user_summary_from_csv(&cfg.output_user_csv, &cfg.summary)
+            {
+                eprintln!("Failed to print user notification summary: {e}");
+            }
+        }
+    }
+    outcome?;
+
+    println!("[{}] Done.", Utc::now().to_rfc3339());
+    Ok(())
+}
+
+/// Connection settings of the Deribit client.
+fn client_settings(cfg: &Config) -> ClientSettings {
+    ClientSettings {
+        endpoint: Endpoint {
+            url: cfg.endpoint_url.clone(),
+            tls_ca_file: cfg.tls_ca_file.clone(),
+            tls_server_name: cfg.tls_server_name.clone(),
+        },
+        client_id: cfg.client_id.clone(),
+        client_secret: cfg.client_secret.clone(),
+        reconnect: ReconnectPolicy {
+            enabled: cfg.reconnect,
+            initial_backoff: cfg.reconnect_initial_backoff,
+            max_backoff: cfg.reconnect_max_backoff,
+            max_attempts: cfg.reconnect_max_attempts,
+        },
+        request_timeout: cfg.request_timeout,
+        heartbeat_interval: cfg.heartbeat_interval,
+        cancel_on_disconnect: cfg
+            .cancel_on_disconnect
+            .then_some(cfg.cancel_on_disconnect_scope),
+    }
+}
+
+/// Check `num_iterations` times per instrument that Deribit cancels a resting order when
+/// the connection drops: place the order, drop the socket, reconnect and poll
+/// `private/get_open_orders_by_instrument` until the order is gone. Cancel-on-disconnect is
+/// enabled with `cancel_on_disconnect_scope` whether or not `cancel_on_disconnect` is set.
+async fn cancel_on_disconnect_test(cfg: Config) -> Result<()> {
+    if !cfg.reconnect {
+        anyhow::bail!("cancel-on-disconnect-test needs reconnect = true");
+    }
+    let scope = cfg.cancel_on_disconnect_scope;
     println!(
-        "[{}] Using base price ~{} for order placement",
+        "[{}] Checking cancel-on-disconnect (scope={}, instruments={}, endpoint={})",
         Utc::now().to_rfc3339(),
-        base_price
+        scope.as_str(),
+        instrument_names(&cfg).join(","),
+        cfg.endpoint_url
     );
 
-    // Prepare latency logger
-    let mut logger = LatencyLogger::new(&cfg.output_latency_csv, program_start)?;
+    // Neither market data nor connection events are used here
+    let (md_tx, _md_rx) = mpsc::unbounded_channel::<MarketDataEvent>();
+    let (client_event_tx, _client_event_rx) = mpsc::unbounded_channel::<ClientEvent>();
+    let mut settings = client_settings(&cfg);
+    settings.cancel_on_disconnect = Some(scope);
+    let client = DeribitClient::connect(settings, md_tx, client_event_tx).await?;
     println!(
-        "[{}] Writing latency samples to {}",
-        Utc::now().to_rfc3339(),
-        cfg.output_latency_csv
+        "[{}] Connected and authenticated, cancel-on-disconnect enabled.",
+        Utc::now().to_rfc3339()
     );
 
-    // Shared state for order id
-    let order_id_state = Arc::new(Mutex::new(None::<String>));
+    let mut prices = Vec::with_capacity(cfg.instruments.len());
+    for instrument in &cfg.instruments {
+        let name = &instrument.instrument_name;
+        let tick_size = fetch_tick_size(&client, name).await?;
+        let base_price = fetch_ticker_price(&client, name)
+            .await
+            .unwrap_or(instrument.base_price);
+        let price = base_price * (1.0 + instrument.price_offset_percent / 100.0);
+        prices.push(quantize_price(price, tick_size));
+    }
 
-    run_roundtrip_test(
-        &mut client,
-        &cfg,
-        tick_size,
-        base_price,
-        &last_tick_ns,
-        &mut logger,
-        &order_id_state,
-    )
-    .await?;
+    let mut writer = create_csv_writer(&cfg.output_cancel_on_disconnect_csv)?;
+    let label = format!("{}-cod", cfg.run_label);
+    let total = cfg.num_iterations * cfg.instruments.len();
+    let mut still_open = 0;
+    for check in 0..total {
+        let slot = check % cfg.instruments.len();
+        let instrument = &cfg.instruments[slot];
+        let name = instrument.instrument_name.as_str();
 
-    if cfg.print_summary {
-        if let Err(e) = summary::print_summary_from_csv(&cfg.output_latency_csv) {
-            eprintln!("Failed to print summary: {e}");
+        let method = match instrument.side {
+            OrderSide::Buy => "private/buy",
+            OrderSide::Sell => "private/sell",
+        };
+        let params = json!({
+            "instrument_name": name,
+            "amount": instrument.order_amount,
+            "type": "limit",
+            "price": prices[slot],
+            "post_only": true,
+            "label": label,
+        });
+        let resp = client.send_rpc(method, params).await?;
+        let order_id = resp
+            .result_or_error()
+            .context("placing the order failed")?
+            .get("order")
+            .and_then(|o| o.get("order_id"))
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("no order_id in the response"))?
+            .to_string();
+        println!(
+            "[{}] Check {}/{} {}: order {} open, dropping the connection ...",
+            Utc::now().to_rfc3339(),
+            check + 1,
+            total,
+            name,
+            order_id
+        );
+
+        let sample = check_order_cancelled(&client, check + 1, scope, name, &order_id).await?;
+        match sample.cleanup_us {
+            Some(cleanup_us) => println!(
+                "[{}] Order {} cancelled: gone {} µs after the drop (reconnected after {} µs)",
+                Utc::now().to_rfc3339(),
+                order_id,
+                cleanup_us,
+                sample.reconnect_us
+            ),
+            None => {
+                still_open += 1;
+                eprintln!("Order {order_id} is still open after the reconnect, cancelling it");
+                let resp = client
+                    .send_rpc("private/cancel", json!({ "order_id": order_id }))
+                    .await?;
+                if let Err(e) = resp.result_or_error() {
+                    eprintln!("Failed to cancel order {order_id}: {e}");
+                }
+            }
         }
+        writer.serialize(&sample)?;
+        writer.flush()?;
+
+        sleep(cfg.sleep_between_requests).await;
     }
 
+    if cfg.print_summary {
+        summary::print_cancel_on_disconnect_summary_from_csv(
+            &cfg.output_cancel_on_disconnect_csv,
+            &cfg.summary,
+        )?;
+    }
+    if still_open > 0 {
+        anyhow::bail!("cancel-on-disconnect left {still_open} of {total} orders open");
+    }
     println!("[{}] Done.", Utc::now().to_rfc3339());
     Ok(())
 }
 
-async fn fetch_tick_size(client: &mut DeribitClient, instrument: &str) -> Result<f64> {
+/// Turn the first SIGINT (Ctrl-C) or SIGTERM into a shutdown request: the chains abandon
+/// their iterations and cancel their orders, and the partial run is summarized. A second
+/// signal exits immediately.
+fn spawn_shutdown_listener(shutdown_tx: watch::Sender<bool>) {
+    tokio::spawn(async move {
+        let signal = wait_for_signal().await;
+        println!(
+            "[{}] {} received, cancelling this run's orders and shutting down (send it again to exit immediately) ...",
+            Utc::now().to_rfc3339(),
+            signal
+        );
+        shutdown_tx.send_replace(true);
+
+        let signal = wait_for_signal().await;
+        eprintln!("{signal} received again, exiting without cleanup");
+        std::process::exit(130);
+    });
+}
+
+/// Wait for SIGINT or SIGTERM and return its name.
+#[cfg(unix)]
+async fn wait_for_signal() -> &'static str {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    match signal(SignalKind::terminate()) {
+        Ok(mut sigterm) => tokio::select! {
+            _ = tokio::signal::ctrl_c() => "SIGINT",
+            _ = sigterm.recv() => "SIGTERM",
+        },
+        Err(_) => {
+            let _ = tokio::signal::ctrl_c().await;
+            "SIGINT"
+        }
+    }
+}
+
+/// Wait for Ctrl-C and return its name.
+#[cfg(not(unix))]
+async fn wait_for_signal() -> &'static str {
+    let _ = tokio::signal::ctrl_c().await;
+    "Ctrl-C"
+}
+
+/// Resolve once a shutdown has been requested.
+async fn shutdown_requested(mut shutdown: watch::Receiver<bool>) {
+    if shutdown.wait_for(|requested| *requested).await.is_err() {
+        // Nobody can request a shutdown any more
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Names of the traded instruments, in config order.
+fn instrument_names(cfg: &Config) -> Vec<&str> {
+    cfg.instruments
+        .iter()
+        .map(|i| i.instrument_name.as_str())
+        .collect()
+}
+
+/// Instrument traded in this run, with the values fetched for it at startup.
+struct TradedInstrument {
+    cfg: InstrumentConfig,
+    tick_size: f64,
+    /// Ticker price at startup (or the configured `base_price`).
+    base_price: f64,
+    /// Ticks of this instrument's raw book firing orders in tick-to-trade mode.
+    tick_triggers: Option<TriggerReceiver>,
+}
+
+async fn fetch_tick_size(client: &DeribitClient, instrument: &str) -> Result<f64> {
     let params = serde_json::json!({ "instrument_name": instrument });
     let resp = client.send_rpc("public/get_instrument", params).await?;
-    if let Some(err) = resp.error {
-        return Err(anyhow!("get_instrument error: {:?}", err));
-    }
-    let tick_size = resp
-        .result
-        .as_ref()
-        .and_then(|r| r.get("tick_size"))
+    let result = resp.result_or_error().context("get_instrument failed")?;
+    let tick_size = result
+        .get("tick_size")
         .and_then(|v| v.as_f64())
         .unwrap_or(0.5);
     Ok(tick_size)
 }
 
-async fn fetch_ticker_price(client: &mut DeribitClient, instrument: &str) -> Result<f64> {
+async fn fetch_ticker_price(client: &DeribitClient, instrument: &str) -> Result<f64> {
     let params = serde_json::json!({ "instrument_name": instrument });
     let resp = client.send_rpc("public/ticker", params).await?;
-    if let Some(err) = resp.error {
-        return Err(anyhow!("ticker error: {:?}", err));
-    }
-    let price = resp
-        .result
-        .as_ref()
-        .and_then(|r| r.get("mark_price").or_else(|| r.get("last_price")))
+    let result = resp.result_or_error().context("ticker failed")?;
+    let price = result
+        .get("mark_price")
+        .or_else(|| result.get("last_price"))
         .and_then(|v| v.as_f64())
         .ok_or_else(|| anyhow!("no mark_price / last_price in ticker"))?;
     Ok(price)
@@ -176,170 +620,663 @@ fn quantize_price(price: f64, tick_size: f64) -> f64 {
     steps * tick_size
 }
 
-/// Run a sequence of (side + edit + cancel) iterations and log all latencies.
+/// Run `num_iterations` scenario iterations (by default open → edit → cancel) per
+/// instrument and log all latencies.
+///
+/// The iterations are shared by `concurrent_chains` independent chains, so up to that
+/// many requests are in flight at once (one per chain). Round-robin mode runs one set of
+/// chains whose iterations rotate through the instruments; parallel mode runs a set of
+/// chains per instrument, all at the same time. In tick-to-trade mode every open and edit
+/// waits for the next firing raw book tick of its instrument.
+///
+/// A shutdown request, or a chain failing, stops all chains after they cancelled the
+/// orders of their current iteration; the run then fails as incomplete.
 async fn run_roundtrip_test(
-    client: &mut DeribitClient,
+    client: &DeribitClient,
     cfg: &Config,
-    tick_size: f64,
-    base_price: f64,
-    last_tick_ns: &Arc<RwLock<Option<i64>>>,
-    logger: &mut LatencyLogger,
-    order_id_state: &Arc<Mutex<Option<String>>>,
+    instruments: &[TradedInstrument],
+    market: &Arc<RwLock<MarketState>>,
+    logger: &Arc<Mutex<LatencyLogger>>,
+    shutdown_tx: &watch::Sender<bool>,
 ) -> Result<()> {
-    for i in 0..cfg.num_iterations {
-        let iteration_start = Utc::now().to_rfc3339();
-        println!(
-            "[{}] Iteration {}/{}",
-            iteration_start,
-            i + 1,
-            cfg.num_iterations
-        );
+    let started = Instant::now();
 
-        // --- NEW ORDER ---
-        let open_price_raw = base_price * (1.0 + cfg.price_offset_percent / 100.0); // Offset price relative to the base price
-        let open_price = quantize_price(open_price_raw, tick_size);
-
-        // Decide side and RPC method based on configuration
-        let (open_op_type, open_method) = match cfg.side {
-            OrderSide::Buy => ("buy", "private/buy"),
-            OrderSide::Sell => ("sell", "private/sell"),
-        };
+    // Instruments sharing one iteration counter and set of chains
+    let groups: Vec<&[TradedInstrument]> = match cfg.instrument_mode {
+        InstrumentMode::RoundRobin => vec![instruments],
+        InstrumentMode::Parallel => instruments.chunks(1).collect(),
+    };
+    let counters: Vec<Iterations> = groups.iter().map(|_| Iterations::default()).collect();
 
-        let open_params = json!({
-            "instrument_name": cfg.instrument_name,
-            "amount": cfg.order_amount,
-            "type": "limit",
-            "price": open_price,
-            "post_only": true,
+    let chains = groups
+        .iter()
+        .zip(&counters)
+        .flat_map(|(group, iterations)| {
+            (0..cfg.concurrent_chains).map(move |chain| async move {
+                let shutdown = shutdown_tx.subscribe();
+                let result = run_chain(
+                    client, cfg, group, market, logger, iterations, shutdown, chain,
+                )
+                .await;
+                if result.is_err() {
+                    shutdown_tx.send_replace(true);
+                }
+                result
+            })
         });
+    let results = futures::future::join_all(chains).await;
+
+    let total = cfg.num_iterations * instruments.len();
+    let completed: usize = counters
+        .iter()
+        .map(|c| c.completed.load(Ordering::Relaxed))
+        .sum();
+    println!(
+        "[{}] Completed {} of {} iterations on {} instrument(s) and {} chain(s) in {:.3} s",
+        Utc::now().to_rfc3339(),
+        completed,
+        total,
+        instruments.len(),
+        cfg.concurrent_chains * groups.len(),
+        started.elapsed().as_secs_f64()
+    );
+
+    results.into_iter().collect::<Result<Vec<()>>>()?;
+    if completed < total {
+        anyhow::bail!("run interrupted after {completed} of {total} iterations");
+    }
+    Ok(())
+}
+
+/// Iteration counters shared by the chains of one instrument group.
+#[derive(Default)]
+struct Iterations {
+    /// Next iteration number to claim.
+    next: AtomicUsize,
+    /// Iterations run to the end (including abandoned ones).
+    completed: AtomicUsize,
+}
 
-        let open_resp = timed_rpc(
+/// One chain of sequential iterations, taking iteration numbers from `next_iteration`
+/// until all iterations of `instruments` are claimed; iteration `i` trades
+/// `instruments[i % instruments.len()]`.
+///
+/// An iteration interrupted by a lost connection or a request timeout is abandoned:
+/// the chain waits for the client to reconnect (if needed), cancels the orders left over
+/// from that iteration (if known) and continues with the next iteration. On a shutdown
+/// request or any other error the chain cancels them and stops.
+#[allow(clippy::too_many_arguments)]
+async fn run_chain(
+    client: &DeribitClient,
+    cfg: &Config,
+    instruments: &[TradedInstrument],
+    market: &Arc<RwLock<MarketState>>,
+    logger: &Arc<Mutex<LatencyLogger>>,
+    iterations: &Iterations,
+    shutdown: watch::Receiver<bool>,
+    chain: usize,
+) -> Result<()> {
+    // Open orders of this chain's current iteration
+    let open_orders: &OpenOrders = &Arc::new(Mutex::new(BTreeMap::new()));
+    let mut tick_triggers: Vec<Option<TriggerReceiver>> = instruments
+        .iter()
+        .map(|i| i.tick_triggers.clone())
+        .collect();
+    let total = cfg.num_iterations * instruments.len();
+
+    loop {
+        if *shutdown.borrow() {
+            return Ok(());
+        }
+        let i = iterations.next.fetch_add(1, Ordering::Relaxed);
+        if i >= total {
+            return Ok(());
+        }
+        let slot = i % instruments.len();
+        let instrument = &instruments[slot];
+
+        let mut label = format!("Iteration {}/{}", i + 1, total);
+        if cfg.instruments.len() > 1 {
+            label.push_str(&format!(" {}", instrument.cfg.instrument_name));
+        }
+        if cfg.concurrent_chains > 1 {
+            label.push_str(&format!(" (chain {})", chain + 1));
+        }
+        println!("[{}] {}", Utc::now().to_rfc3339(), label);
+
+        let scenario = run_scenario(
             client,
-            open_op_type,
-            open_method,
-            &cfg.instrument_name,
-            None,
-            last_tick_ns,
+            cfg,
+            instrument,
+            market,
+            tick_triggers[slot].as_mut(),
             logger,
-            open_params,
+            open_orders,
+        );
+        let outcome = tokio::select! {
+            outcome = scenario => outcome,
+            _ = shutdown_requested(shutdown.clone()) => {
+                println!(
+                    "[{}] Abandoning iteration {} for shutdown",
+                    Utc::now().to_rfc3339(),
+                    i + 1
+                );
+                cancel_leftover_orders(
+                    client,
+                    &instrument.cfg.instrument_name,
+                    market,
+                    logger,
+                    open_orders,
+                )
+                .await?;
+                return Ok(());
+            }
+        };
+
+        let mut failure = None;
+        if let Err(e) = outcome {
+            match e.downcast_ref::<ClientError>() {
+                Some(ClientError::ConnectionLost) => {
+                    println!(
+                        "[{}] Connection lost during iteration {}, waiting for reconnect ...",
+                        Utc::now().to_rfc3339(),
+                        i + 1
+                    );
+                    tokio::select! {
+                        connected = client.wait_connected() => connected?,
+                        _ = shutdown_requested(shutdown.clone()) => {}
+                    }
+                }
+                Some(ClientError::Timeout(limit)) => {
+                    println!(
+                        "[{}] Request timed out after {:?} during iteration {}",
+                        Utc::now().to_rfc3339(),
+                        limit,
+                        i + 1
+                    );
+                }
+                _ => failure = Some(e),
+            }
+        }
+        cancel_leftover_orders(
+            client,
+            &instrument.cfg.instrument_name,
+            market,
+            logger,
+            open_orders,
         )
         .await?;
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        iterations.completed.fetch_add(1, Ordering::Relaxed);
 
-        // Extract order_id if present
-        if let Some(result) = open_resp.result.as_ref() {
-            if let Some(order) = result.get("order") {
-                if let Some(oid) = order.get("order_id").and_then(|v| v.as_str()) {
-                    let mut guard = order_id_state.lock().await;
-                    *guard = Some(oid.to_string());
-                }
+        tokio::select! {
+            _ = sleep(cfg.sleep_between_requests) => {}
+            _ = shutdown_requested(shutdown.clone()) => {}
+        }
+    }
+}
+
+/// Order opened by a scenario step, tracked until it is cancelled.
+#[derive(Debug, Clone)]
+struct TrackedOrder {
+    order_id: String,
+    side: OrderSide,
+    amount: f64,
+    /// Price offset of the last open / edit; the next edit moves on from it.
+    offset_percent: f64,
+    label: String,
+}
+
+/// Orders of a chain's current iteration that are still open, by scenario `order` name.
+type OpenOrders = Arc<Mutex<BTreeMap<String, TrackedOrder>>>;
+
+/// One run of the scenario steps on `instrument`.
+async fn run_scenario(
+    client: &DeribitClient,
+    cfg: &Config,
+    instrument: &TradedInstrument,
+    market: &Arc<RwLock<MarketState>>,
+    mut tick_triggers: Option<&mut TriggerReceiver>,
+    logger: &Arc<Mutex<LatencyLogger>>,
+    open_orders: &OpenOrders,
+) -> Result<()> {
+    let steps = &cfg.scenario_steps;
+    for (i, step) in steps.iter().enumerate() {
+        for k in 0..step.repeat {
+            if step.action != StepAction::Wait {
+                run_step(
+                    client,
+                    cfg,
+                    instrument,
+                    step,
+                    &step.order_name(k),
+                    market,
+                    tick_triggers.as_deref_mut(),
+                    logger,
+                    open_orders,
+                )
+                .await?;
+                continue;
+            }
+
+            // In tick-to-trade mode the next step waits for its tick instead of the pacing
+            let next_on_tick = tick_triggers.is_some()
+                && steps.get(i + 1).is_some_and(|s| s.action.fires_on_tick());
+            match step.wait_ms {
+                Some(ms) => sleep(Duration::from_millis(ms)).await,
+                None if !next_on_tick => sleep(cfg.sleep_between_requests).await,
+                None => {}
             }
         }
+    }
+    Ok(())
+}
 
-        sleep(cfg.sleep_between_requests).await;
+/// Send the request of one scenario step (other than `wait`) on the order named
+/// `order_name`, logged with the step's `op_type`.
+#[allow(clippy::too_many_arguments)]
+async fn run_step(
+    client: &DeribitClient,
+    cfg: &Config,
+    instrument: &TradedInstrument,
+    step: &ScenarioStep,
+    order_name: &str,
+    market: &Arc<RwLock<MarketState>>,
+    tick_triggers: Option<&mut TriggerReceiver>,
+    logger: &Arc<Mutex<LatencyLogger>>,
+    open_orders: &OpenOrders,
+) -> Result<()> {
+    let order = &instrument.cfg;
+    let name = order.instrument_name.as_str();
+    let op_type = step.op_type.as_deref();
+    let tracked = open_orders.lock().await.get(order_name).cloned();
+
+    match (step.action, tracked) {
+        (StepAction::Open | StepAction::Buy | StepAction::Sell, _) => {
+            // In tick-to-trade mode the order is fired by the next triggering tick
+            let tick = next_tick(market, tick_triggers).await?;
+
+            let side = match step.action {
+                StepAction::Buy => OrderSide::Buy,
+                StepAction::Sell => OrderSide::Sell,
+                _ => order.side,
+            };
+            let amount = step.amount.unwrap_or(order.order_amount);
+            // The instrument's offset is signed for its side: mirror it for the other side
+            // so a buy stays below and a sell above the market
+            let offset_percent = step.price_offset_percent.unwrap_or(if side == order.side {
+                order.price_offset_percent
+            } else {
+                -order.price_offset_percent
+            });
+            let reference = reference_price(cfg, instrument, side, market).await;
+            let price_raw = reference * (1.0 + offset_percent / 100.0); // Offset price relative to the reference price
+            let price = quantize_price(price_raw, instrument.tick_size);
 
-        // --- EDIT ORDER (private/edit) ---
-        let maybe_order_id = { order_id_state.lock().await.clone() };
+            let (default_op_type, method) = match side {
+                OrderSide::Buy => ("buy", "private/buy"),
+                OrderSide::Sell => ("sell", "private/sell"),
+            };
+            let label = order_label(cfg, step);
+            let params = json!({
+                "instrument_name": name,
+                "amount": amount,
+                "type": "limit",
+                "price": price,
+                "post_only": true,
+                "label": label,
+            });
+            let resp = timed_rpc(
+                client,
+                op_type.unwrap_or(default_op_type),
+                method,
+                name,
+                None,
+                tick,
+                logger,
+                params,
+            )
+            .await?;
+
+            // Extract order_id if present
+            let order_id = resp
+                .result
+                .as_ref()
+                .and_then(|r| r.get("order"))
+                .and_then(|o| o.get("order_id"))
+                .and_then(|v| v.as_str());
+            if let Some(order_id) = order_id {
+                let tracked = TrackedOrder {
+                    order_id: order_id.to_string(),
+                    side,
+                    amount,
+                    offset_percent,
+                    label,
+                };
+                open_orders
+                    .lock()
+                    .await
+                    .insert(order_name.to_string(), tracked);
+            }
+        }
+        (StepAction::Edit, Some(mut tracked)) => {
+            let tick = next_tick(market, tick_triggers).await?;
 
-        if let Some(ref order_id) = maybe_order_id {
             // Move the quote further away from the market on each edit
             // For buys: more negative offset (further below the market)
             // For sells: more positive offset (further above the market)
-            let edit_offset_percent = match cfg.side {
-                OrderSide::Buy => cfg.price_offset_percent - cfg.edit_offset_step_percent,
-                OrderSide::Sell => cfg.price_offset_percent + cfg.edit_offset_step_percent,
-            };
+            tracked.offset_percent = step.price_offset_percent.unwrap_or(match tracked.side {
+                OrderSide::Buy => tracked.offset_percent - order.edit_offset_step_percent,
+                OrderSide::Sell => tracked.offset_percent + order.edit_offset_step_percent,
+            });
+            tracked.amount = step.amount.unwrap_or(tracked.amount);
 
-            let new_price_raw = base_price * (1.0 + edit_offset_percent / 100.0); // Small tweak
-            let new_price = quantize_price(new_price_raw, tick_size);
+            let reference = reference_price(cfg, instrument, tracked.side, market).await;
+            let new_price_raw = reference * (1.0 + tracked.offset_percent / 100.0);
+            let new_price = quantize_price(new_price_raw, instrument.tick_size);
 
-            let edit_params = json!({
-                "order_id": order_id,
-                "amount": cfg.order_amount,
+            let params = json!({
+                "order_id": tracked.order_id,
+                "amount": tracked.amount,
                 "price": new_price,
             });
-
-            let _edit_resp = timed_rpc(
+            timed_rpc(
                 client,
-                "edit",
+                op_type.unwrap_or("edit"),
                 "private/edit",
-                &cfg.instrument_name,
-                Some(order_id.as_str()),
-                last_tick_ns,
+                name,
+                Some(tracked.order_id.as_str()),
+                tick,
                 logger,
-                edit_params,
+                params,
             )
             .await?;
-
-            sleep(cfg.sleep_between_requests).await;
-
-            // --- CANCEL ORDER (private/cancel) ---
-            let cancel_params = json!({
-                "order_id": order_id,
+            open_orders
+                .lock()
+                .await
+                .insert(order_name.to_string(), tracked);
+        }
+        (StepAction::Cancel, Some(tracked)) => {
+            let params = json!({
+                "order_id": tracked.order_id,
             });
-
-            let _cancel_resp = timed_rpc(
+            let tick = market.read().await.last_tick_ns;
+            timed_rpc(
                 client,
-                "cancel",
+                op_type.unwrap_or("cancel"),
                 "private/cancel",
-                &cfg.instrument_name,
-                Some(order_id.as_str()),
-                last_tick_ns,
+                name,
+                Some(tracked.order_id.as_str()),
+                tick,
                 logger,
-                cancel_params,
+                params,
+            )
+            .await?;
+            open_orders.lock().await.remove(order_name);
+        }
+        (StepAction::GetOrderState, Some(tracked)) => {
+            let params = json!({
+                "order_id": tracked.order_id,
+            });
+            let tick = market.read().await.last_tick_ns;
+            timed_rpc(
+                client,
+                op_type.unwrap_or("get_order_state"),
+                "private/get_order_state",
+                name,
+                Some(tracked.order_id.as_str()),
+                tick,
+                logger,
+                params,
+            )
+            .await?;
+        }
+        (action, _) if action.cancels_all() || action == StepAction::CancelByLabel => {
+            let (method, params) = match action {
+                StepAction::CancelAllByInstrument => (
+                    "private/cancel_all_by_instrument",
+                    json!({ "instrument_name": name }),
+                ),
+                StepAction::CancelAllByCurrency => (
+                    "private/cancel_all_by_currency",
+                    json!({ "currency": order.currency() }),
+                ),
+                StepAction::CancelByLabel => (
+                    "private/cancel_by_label",
+                    json!({ "label": order_label(cfg, step) }),
+                ),
+                _ => ("private/cancel_all", json!({})),
+            };
+            let tick = market.read().await.last_tick_ns;
+            timed_rpc(
+                client,
+                op_type.unwrap_or(action.as_str()),
+                method,
+                name,
+                None,
+                tick,
+                logger,
+                params,
             )
             .await?;
 
-            // Clear order id state after cancel
-            {
-                let mut guard = order_id_state.lock().await;
-                *guard = None;
+            let mut open = open_orders.lock().await;
+            match action {
+                StepAction::CancelByLabel => {
+                    let label = order_label(cfg, step);
+                    open.retain(|_, o| o.label != label);
+                }
+                _ => open.clear(),
             }
-        } else {
-            println!(
-                "[{}] No active order_id to edit/cancel.",
-                Utc::now().to_rfc3339()
-            );
         }
+        (StepAction::GetOpenOrders, _) => {
+            let params = json!({
+                "instrument_name": name,
+            });
+            let tick = market.read().await.last_tick_ns;
+            timed_rpc(
+                client,
+                op_type.unwrap_or("get_open_orders"),
+                "private/get_open_orders_by_instrument",
+                name,
+                None,
+                tick,
+                logger,
+                params,
+            )
+            .await?;
+        }
+        // The order's open returned no order_id
+        (action, _) => println!(
+            "[{}] No active order_id for '{}', skipping {}.",
+            Utc::now().to_rfc3339(),
+            order_name,
+            action.as_str()
+        ),
+    }
 
-        sleep(cfg.sleep_between_requests).await;
+    Ok(())
+}
+
+/// Label of the orders opened (or cancelled) by `step`: the run's label, plus the step's
+/// own label if it has one.
+fn order_label(cfg: &Config, step: &ScenarioStep) -> String {
+    match &step.label {
+        Some(label) => format!("{}-{}", cfg.run_label, label),
+        None => cfg.run_label.clone(),
+    }
+}
+
+/// Cancel the open orders on `instruments` whose label starts with `label_prefix`, with
+/// one `private/cancel_by_label` per label found. Not logged as latency samples.
+async fn cancel_labelled_orders(
+    client: &DeribitClient,
+    instruments: &[TradedInstrument],
+    label_prefix: &str,
+) -> Result<()> {
+    let mut labels = BTreeSet::new();
+    for instrument in instruments {
+        let params = json!({ "instrument_name": instrument.cfg.instrument_name });
+        let resp = client
+            .send_rpc("private/get_open_orders_by_instrument", params)
+            .await?;
+        let orders = resp
+            .result_or_error()
+            .context("get_open_orders_by_instrument failed")?;
+        let found = orders
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|order| order.get("label")?.as_str())
+            .filter(|label| label.starts_with(label_prefix));
+        labels.extend(found.map(str::to_string));
     }
 
+    for label in labels {
+        let resp = client
+            .send_rpc("private/cancel_by_label", json!({ "label": label }))
+            .await?;
+        let cancelled = resp.result_or_error().context("cancel_by_label failed")?;
+        println!(
+            "[{}] Cancelled {} leftover order(s) labelled {}",
+            Utc::now().to_rfc3339(),
+            cancelled,
+            label
+        );
+    }
     Ok(())
 }
 
+/// Cancel the orders an iteration left open (scenario without a final cancel, or
+/// interrupted), if their order_ids are known.
+async fn cancel_leftover_orders(
+    client: &DeribitClient,
+    instrument_name: &str,
+    market: &Arc<RwLock<MarketState>>,
+    logger: &Arc<Mutex<LatencyLogger>>,
+    open_orders: &OpenOrders,
+) -> Result<()> {
+    let leftovers = std::mem::take(&mut *open_orders.lock().await);
+
+    for tracked in leftovers.into_values() {
+        let order_id = tracked.order_id;
+        println!(
+            "[{}] Cancelling leftover order {}",
+            Utc::now().to_rfc3339(),
+            order_id
+        );
+        let cancel_params = json!({
+            "order_id": order_id,
+        });
+        // Read the tick on its own so the book lock is not held for the round trip
+        let tick = market.read().await.last_tick_ns;
+        let result = timed_rpc(
+            client,
+            "cancel",
+            "private/cancel",
+            instrument_name,
+            Some(order_id.as_str()),
+            tick,
+            logger,
+            cancel_params,
+        )
+        .await;
+
+        // Best effort: a failed cleanup must not abort the remaining iterations
+        if let Err(e) = result {
+            eprintln!("Failed to cancel leftover order {order_id}: {e}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Price the order offsets are applied to: with `price_from_book`, the same-side touch of
+/// the live local book (best bid for buys, best ask for sells), falling back to its mid;
+/// otherwise, or while the book is still empty, the ticker price fetched at startup.
+async fn reference_price(
+    cfg: &Config,
+    instrument: &TradedInstrument,
+    side: OrderSide,
+    market: &Arc<RwLock<MarketState>>,
+) -> f64 {
+    if !cfg.price_from_book {
+        return instrument.base_price;
+    }
+    let name = &instrument.cfg.instrument_name;
+    let guard = market.read().await;
+    guard
+        .touch_price(name, side)
+        .or_else(|| guard.books.get(name)?.mid())
+        .unwrap_or(instrument.base_price)
+}
+
+/// Tick a request is aligned to: in tick-to-trade mode (`tick_triggers` given) the next
+/// firing tick, waited for here; otherwise the latest tick received so far.
+async fn next_tick(
+    market: &Arc<RwLock<MarketState>>,
+    tick_triggers: Option<&mut TriggerReceiver>,
+) -> Result<Option<i64>> {
+    let Some(rx) = tick_triggers else {
+        return Ok(market.read().await.last_tick_ns);
+    };
+
+    // Only a tick arriving from now on may fire the request
+    rx.borrow_and_update();
+    rx.changed()
+        .await
+        .map_err(|_| anyhow!("market data feed ended"))?;
+    let tick = *rx.borrow();
+    Ok(tick.map(|t| t.recv_ts_mono_ns))
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn timed_rpc(
-    client: &mut DeribitClient,
+    client: &DeribitClient,
     op_type: &str,
     rpc_method: &str,
     instrument_name: &str,
     order_id: Option<&str>,
-    last_tick_ns: &Arc<RwLock<Option<i64>>>,
-    logger: &mut LatencyLogger,
+    tick_ts_mono_ns: Option<i64>,
+    logger: &Arc<Mutex<LatencyLogger>>,
     params: serde_json::Value,
 ) -> Result<RpcResponse> {
-    let tick_ts_mono_ns_opt = {
-        let guard = last_tick_ns.read().await;
-        *guard
-    };
+    let label = params
+        .get("label")
+        .and_then(|v| v.as_str())
+        .map(str::to_string);
 
-    let send_ts_wall = Utc::now();
-    let send_ts_mono = Instant::now();
+    // The RTT starts right after the frame was written to the socket; serialization
+    // and write time are logged separately
+    let pending = client.send(rpc_method, params).await?;
+    let sent = pending.sent;
 
-    let resp = client.send_rpc(rpc_method, params).await?;
+    let result = pending.response().await;
+
+    // Timeouts are logged too, so tail latency is not silently dropped
+    let outcome = match &result {
+        Ok(resp) => SampleOutcome::Response(resp),
+        Err(ClientError::Timeout(_)) => SampleOutcome::Timeout {
+            ts_mono: Instant::now(),
+            ts_wall: Utc::now(),
+        },
+        Err(_) => return result.map_err(Into::into),
+    };
 
     let sample_ctx = SampleContext {
         op_type,
         rpc_method,
         instrument_name,
         order_id,
-        tick_ts_mono_ns: tick_ts_mono_ns_opt,
-        send_ts_mono,
-        send_ts_wall,
-        resp: &resp,
+        label: label.as_deref(),
+        tick_ts_mono_ns,
+        sent,
+        outcome,
     };
 
-    logger.log_sample(sample_ctx)?;
+    logger.lock().await.log_sample(sample_ctx)?;
 
-    Ok(resp)
+    Ok(result?)
 }
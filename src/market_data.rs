@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::Utc;
+use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use tokio::task::JoinHandle;
+
+use crate::config::OrderSide;
+use crate::deribit_client::{DeribitClient, MarketDataEvent};
+use crate::latency::{FeedLatencyLogger, LatencyLogger};
+use crate::order_book::{ApplyOutcome, OrderBook};
+use crate::tick_trigger::{TickTrigger, TriggerTick};
+
+/// Market data shared between the market data task and the order chains.
+#[derive(Debug, Default)]
+pub struct MarketState {
+    /// Monotonic receive time of the latest raw book tick (ns since program start).
+    pub last_tick_ns: Option<i64>,
+    /// Local order book per instrument, built from its `book.<instrument>.raw` updates.
+    pub books: HashMap<String, OrderBook>,
+}
+
+impl MarketState {
+    /// Same-side best price of the live book: best bid for buys, best ask for sells.
+    pub fn touch_price(&self, instrument: &str, side: OrderSide) -> Option<f64> {
+        let book = self.books.get(instrument)?;
+        match side {
+            OrderSide::Buy => book.best_bid(),
+            OrderSide::Sell => book.best_ask(),
+        }
+    }
+}
+
+/// Tick-to-trade trigger evaluated on the book of one instrument (one per traded instrument).
+pub struct TriggerSetup {
+    pub instrument: String,
+    pub trigger: TickTrigger,
+    pub tx: watch::Sender<Option<TriggerTick>>,
+}
+
+/// Spawn the task consuming market data events: it logs their feed latency, tracks the
+/// latest tick, maintains the local order books (detecting `change_id` gaps) and, in
+/// tick-to-trade mode, publishes the ticks that fire orders.
+///
+/// `logger` provides the clock offset estimate used to correct the feed latency and
+/// receives the user channel notifications. With
+/// `resync_client`, a book with a gap is resubscribed through it to get a fresh snapshot.
+pub fn spawn_market_data_task(
+    mut md_rx: mpsc::UnboundedReceiver<MarketDataEvent>,
+    market: Arc<RwLock<MarketState>>,
+    program_start: Instant,
+    mut feed_logger: FeedLatencyLogger,
+    logger: Arc<Mutex<LatencyLogger>>,
+    mut triggers: Vec<TriggerSetup>,
+    resync_client: Option<DeribitClient>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(evt) = md_rx.recv().await {
+            let mut sequence_gap = false;
+
+            // Book and trigger first: logging must not delay a firing tick
+            if let Some(update) = evt.book.as_ref() {
+                let mono_ns = evt.recv_ts_mono.duration_since(program_start).as_nanos() as i64;
+                let mut guard = market.write().await;
+
+                if evt.channel.ends_with(".raw") {
+                    guard.last_tick_ns = Some(mono_ns);
+                    let book = guard
+                        .books
+                        .entry(update.instrument_name.clone())
+                        .or_default();
+                    let outcome = book.apply(update);
+
+                    if let ApplyOutcome::Gap {
+                        expected_prev_change_id,
+                        prev_change_id,
+                    } = outcome
+                    {
+                        sequence_gap = true;
+                        println!(
+                            "[{}] Sequence gap on {}: expected prev_change_id {}, got {:?}",
+                            evt.recv_ts_wall.to_rfc3339(),
+                            evt.channel,
+                            expected_prev_change_id,
+                            prev_change_id
+                        );
+                        if let Some(client) = resync_client.clone() {
+                            book.await_snapshot();
+                            spawn_resync(client, evt.channel.clone());
+                        }
+                    }
+
+                    let setup = triggers
+                        .iter_mut()
+                        .find(|s| s.instrument == update.instrument_name);
+                    if let Some(setup) = setup {
+                        if outcome != ApplyOutcome::Skipped && setup.trigger.on_tick(book) {
+                            let _ = setup.tx.send(Some(TriggerTick {
+                                recv_ts_mono_ns: mono_ns,
+                            }));
+                        }
+                    }
+                }
+            }
+
+            let clock = {
+                let mut guard = logger.lock().await;
+                if let Err(e) = guard.log_user_notification(&evt) {
+                    eprintln!("Failed to log user notification: {e}");
+                }
+                guard.clock_estimate()
+            };
+            if let Err(e) = feed_logger.log(&evt, sequence_gap, clock) {
+                eprintln!("Failed to log market data event: {e}");
+            }
+        }
+    })
+}
+
+/// Resubscribe `channel` so Deribit sends a fresh snapshot. After a reconnect the
+/// channel is restored (with a snapshot) anyway, so failures are only reported.
+fn spawn_resync(client: DeribitClient, channel: String) {
+    tokio::spawn(async move {
+        println!(
+            "[{}] Resyncing {} (resubscribing) ...",
+            Utc::now().to_rfc3339(),
+            channel
+        );
+        match client.resubscribe(std::slice::from_ref(&channel)).await {
+            Ok(resp) if resp.error.is_none() => {}
+            Ok(resp) => eprintln!("Resync of {} failed: {:?}", channel, resp.error),
+            Err(e) => eprintln!("Resync of {channel} failed: {e}"),
+        }
+    });
+}
@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+/// Whether a book notification replaces the book or updates it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookUpdateKind {
+    Snapshot,
+    Change,
+}
+
+/// Action of one price level entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelAction {
+    New,
+    Change,
+    Delete,
+}
+
+/// One `[action, price, amount]` entry of a book notification.
+#[derive(Debug, Clone, Copy)]
+pub struct BookLevel {
+    pub action: LevelAction,
+    pub price: f64,
+    pub amount: f64,
+}
+
+/// Parsed `data` of a `book.<instrument>.*` notification.
+#[derive(Debug, Clone)]
+pub struct BookUpdate {
+    pub instrument_name: String,
+    pub kind: BookUpdateKind,
+    /// Exchange timestamp of the update (milliseconds).
+    pub timestamp_ms: Option<i64>,
+    pub change_id: i64,
+    /// `change_id` of the previous update (absent on snapshots).
+    pub prev_change_id: Option<i64>,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+}
+
+impl BookUpdate {
+    /// Parse the `data` of a book notification; `None` if it is not a book update.
+    pub fn from_data(data: &Value) -> Option<Self> {
+        let kind = match data.get("type").and_then(|t| t.as_str()) {
+            Some("snapshot") => BookUpdateKind::Snapshot,
+            Some("change") => BookUpdateKind::Change,
+            _ => return None,
+        };
+        Some(Self {
+            instrument_name: data.get("instrument_name")?.as_str()?.to_string(),
+            kind,
+            timestamp_ms: data.get("timestamp").and_then(|v| v.as_i64()),
+            change_id: data.get("change_id")?.as_i64()?,
+            prev_change_id: data.get("prev_change_id").and_then(|v| v.as_i64()),
+            bids: parse_levels(data.get("bids")),
+            asks: parse_levels(data.get("asks")),
+        })
+    }
+}
+
+/// Parse `[action, price, amount]` entries, skipping malformed ones.
+fn parse_levels(levels: Option<&Value>) -> Vec<BookLevel> {
+    let Some(levels) = levels.and_then(|l| l.as_array()) else {
+        return Vec::new();
+    };
+    levels
+        .iter()
+        .filter_map(|level| {
+            let action = match level.get(0)?.as_str()? {
+                "new" => LevelAction::New,
+                "change" => LevelAction::Change,
+                "delete" => LevelAction::Delete,
+                _ => return None,
+            };
+            Some(BookLevel {
+                action,
+                price: level.get(1)?.as_f64()?,
+                amount: level.get(2).and_then(|v| v.as_f64()).unwrap_or(0.0),
+            })
+        })
+        .collect()
+}
+
+/// Price level key, ordered by `f64::total_cmp`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Price(f64);
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+/// Result of applying one update to an [`OrderBook`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    /// Applied, but `prev_change_id` did not match the last applied `change_id`:
+    /// at least one update was lost.
+    Gap {
+        expected_prev_change_id: i64,
+        prev_change_id: Option<i64>,
+    },
+    /// Ignored while waiting for a snapshot after [`OrderBook::await_snapshot`].
+    Skipped,
+}
+
+/// Local copy of one instrument's order book, built from raw book updates.
+#[derive(Debug, Default)]
+pub struct OrderBook {
+    bids: BTreeMap<Price, f64>,
+    asks: BTreeMap<Price, f64>,
+    /// `change_id` of the last applied update.
+    pub change_id: Option<i64>,
+    /// Exchange timestamp of the last applied update (milliseconds).
+    pub timestamp_ms: Option<i64>,
+    /// Changes are ignored until the next snapshot (the book is being resynced).
+    awaiting_snapshot: bool,
+}
+
+impl OrderBook {
+    /// Apply an update, checking that it continues the `change_id` sequence.
+    pub fn apply(&mut self, update: &BookUpdate) -> ApplyOutcome {
+        let outcome = match (update.kind, self.change_id) {
+            (BookUpdateKind::Snapshot, _) | (_, None) => ApplyOutcome::Applied,
+            _ if self.awaiting_snapshot => return ApplyOutcome::Skipped,
+            (_, Some(last)) if update.prev_change_id != Some(last) => ApplyOutcome::Gap {
+                expected_prev_change_id: last,
+                prev_change_id: update.prev_change_id,
+            },
+            _ => ApplyOutcome::Applied,
+        };
+        if update.kind == BookUpdateKind::Snapshot {
+            self.bids.clear();
+            self.asks.clear();
+            self.awaiting_snapshot = false;
+        }
+        apply_levels(&mut self.bids, &update.bids);
+        apply_levels(&mut self.asks, &update.asks);
+        self.change_id = Some(update.change_id);
+        self.timestamp_ms = update.timestamp_ms;
+        outcome
+    }
+
+    /// Ignore changes until the next snapshot, e.g. while resubscribing after a gap.
+    pub fn await_snapshot(&mut self) {
+        self.awaiting_snapshot = true;
+    }
+
+    pub fn best_bid(&self) -> Option<f64> {
+        self.bids.keys().next_back().map(|p| p.0)
+    }
+
+    pub fn best_ask(&self) -> Option<f64> {
+        self.asks.keys().next().map(|p| p.0)
+    }
+
+    pub fn mid(&self) -> Option<f64> {
+        Some((self.best_bid()? + self.best_ask()?) / 2.0)
+    }
+}
+
+fn apply_levels(side: &mut BTreeMap<Price, f64>, levels: &[BookLevel]) {
+    for level in levels {
+        if level.action == LevelAction::Delete || level.amount == 0.0 {
+            side.remove(&Price(level.price));
+        } else {
+            side.insert(Price(level.price), level.amount);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(kind: BookUpdateKind, change_id: i64, prev_change_id: Option<i64>) -> BookUpdate {
+        BookUpdate {
+            instrument_name: "BTC-PERPETUAL".to_string(),
+            kind,
+            timestamp_ms: None,
+            change_id,
+            prev_change_id,
+            bids: vec![BookLevel {
+                action: LevelAction::New,
+                price: 100.0 + change_id as f64,
+                amount: 1.0,
+            }],
+            asks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn apply_detects_change_id_gaps() {
+        let mut book = OrderBook::default();
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Snapshot, 10, None)),
+            ApplyOutcome::Applied
+        );
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Change, 11, Some(10))),
+            ApplyOutcome::Applied
+        );
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Change, 13, Some(12))),
+            ApplyOutcome::Gap {
+                expected_prev_change_id: 11,
+                prev_change_id: Some(12),
+            }
+        );
+        // The gapped update is still applied and continues the sequence
+        assert_eq!(book.change_id, Some(13));
+        assert_eq!(book.best_bid(), Some(113.0));
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Change, 14, None)),
+            ApplyOutcome::Gap {
+                expected_prev_change_id: 13,
+                prev_change_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn apply_skips_changes_until_snapshot() {
+        let mut book = OrderBook::default();
+        book.apply(&update(BookUpdateKind::Snapshot, 10, None));
+        book.await_snapshot();
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Change, 12, Some(11))),
+            ApplyOutcome::Skipped
+        );
+        assert_eq!(book.change_id, Some(10));
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Snapshot, 20, None)),
+            ApplyOutcome::Applied
+        );
+        assert_eq!(book.best_bid(), Some(120.0));
+        assert_eq!(
+            book.apply(&update(BookUpdateKind::Change, 21, Some(20))),
+            ApplyOutcome::Applied
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Action of one scenario step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+#[value(rename_all = "snake_case")]
+pub enum StepAction {
+    /// `private/buy` or `private/sell`, following the instrument's `side`.
+    Open,
+    Buy,
+    Sell,
+    Edit,
+    Cancel,
+    /// `private/cancel_all` (every open order of the account).
+    CancelAll,
+    /// `private/cancel_all_by_instrument` for the iteration's instrument.
+    CancelAllByInstrument,
+    /// `private/cancel_all_by_currency` for the currency of the iteration's instrument.
+    CancelAllByCurrency,
+    /// `private/cancel_by_label` for the step's `label`.
+    CancelByLabel,
+    GetOrderState,
+    /// `private/get_open_orders_by_instrument` for the iteration's instrument.
+    GetOpenOrders,
+    Wait,
+}
+
+impl StepAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepAction::Open => "open",
+            StepAction::Buy => "buy",
+            StepAction::Sell => "sell",
+            StepAction::Edit => "edit",
+            StepAction::Cancel => "cancel",
+            StepAction::CancelAll => "cancel_all",
+            StepAction::CancelAllByInstrument => "cancel_all_by_instrument",
+            StepAction::CancelAllByCurrency => "cancel_all_by_currency",
+            StepAction::CancelByLabel => "cancel_by_label",
+            StepAction::GetOrderState => "get_order_state",
+            StepAction::GetOpenOrders => "get_open_orders",
+            StepAction::Wait => "wait",
+        }
+    }
+
+    /// Opens a new order stored under the step's `order` name.
+    pub fn opens_order(self) -> bool {
+        matches!(self, StepAction::Open | StepAction::Buy | StepAction::Sell)
+    }
+
+    /// Needs the order opened by an earlier step.
+    pub fn uses_order(self) -> bool {
+        matches!(
+            self,
+            StepAction::Edit | StepAction::Cancel | StepAction::GetOrderState
+        )
+    }
+
+    /// Cancels every open order of the iteration (all orders of its instrument or more).
+    pub fn cancels_all(self) -> bool {
+        matches!(
+            self,
+            StepAction::CancelAll
+                | StepAction::CancelAllByInstrument
+                | StepAction::CancelAllByCurrency
+        )
+    }
+
+    /// Fired by a raw book tick in tick-to-trade mode.
+    pub fn fires_on_tick(self) -> bool {
+        self.opens_order() || self == StepAction::Edit
+    }
+}
+
+/// One `[[scenario.steps]]` entry.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ScenarioStep {
+    pub action: StepAction,
+    /// Name the opened order is stored under, and that later steps refer to it by
+    /// (`<order>.1` … `<order>.N` for opens with `repeat = N`).
+    #[serde(default = "default_order_ref")]
+    pub order: String,
+    /// `op_type` logged for the step (default: the action, `buy` / `sell` for `open`).
+    #[serde(default)]
+    pub op_type: Option<String>,
+    /// Order amount of open / edit steps (default: the instrument's `order_amount`).
+    #[serde(default)]
+    pub amount: Option<f64>,
+    /// Price offset of open / edit steps. Defaults to the instrument's
+    /// `price_offset_percent` for opens (negated for a `buy` / `sell` on the other side);
+    /// every edit moves the order by `edit_offset_step_percent` from its previous offset.
+    #[serde(default)]
+    pub price_offset_percent: Option<f64>,
+    /// Label of the orders opened by the step (appended to the run's label as
+    /// `<run label>-<label>`); the label cancelled by `cancel_by_label`.
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Run the step this many times in a row (e.g. several edits, or resting orders
+    /// placed before a mass cancel).
+    #[serde(default = "default_repeat")]
+    pub repeat: usize,
+    /// Duration of a `wait` step (default: `sleep_between_requests_secs`).
+    #[serde(default)]
+    pub wait_ms: Option<u64>,
+}
+
+impl ScenarioStep {
+    /// Step with default settings.
+    pub fn new(action: StepAction) -> Self {
+        Self {
+            action,
+            order: default_order_ref(),
+            op_type: None,
+            amount: None,
+            price_offset_percent: None,
+            label: None,
+            repeat: default_repeat(),
+            wait_ms: None,
+        }
+    }
+
+    /// Name of the order opened by the `k`-th (0-based) run of this step.
+    pub fn order_name(&self, k: usize) -> String {
+        if self.repeat > 1 && self.action.opens_order() {
+            format!("{}.{}", self.order, k + 1)
+        } else {
+            self.order.clone()
+        }
+    }
+}
+
+/// The `[scenario]` table.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ScenarioFileConfig {
+    #[serde(default)]
+    pub steps: Vec<ScenarioStep>,
+}
+
+/// Scenario run when none is configured: open → edit → cancel with the configured pacing.
+pub fn default_steps() -> Vec<ScenarioStep> {
+    [
+        StepAction::Open,
+        StepAction::Wait,
+        StepAction::Edit,
+        StepAction::Wait,
+        StepAction::Cancel,
+    ]
+    .into_iter()
+    .map(ScenarioStep::new)
+    .collect()
+}
+
+/// Check the steps, walking them once to make sure every referenced order is open.
+pub fn validate_steps(steps: &[ScenarioStep]) -> Result<()> {
+    // Open order names and their labels
+    let mut open: HashMap<String, Option<&str>> = HashMap::new();
+    for (i, step) in steps.iter().enumerate() {
+        let n = i + 1;
+        if step.repeat == 0 {
+            anyhow::bail!("scenario step {n}: repeat must be at least 1");
+        }
+        if let Some(amount) = step.amount {
+            if amount <= 0.0 || amount.is_nan() {
+                anyhow::bail!("scenario step {n}: amount must be positive (got {amount})");
+            }
+        }
+        if step.wait_ms.is_some() && step.action != StepAction::Wait {
+            anyhow::bail!("scenario step {n}: wait_ms is only valid for wait steps");
+        }
+
+        if step.label.is_some()
+            && !step.action.opens_order()
+            && step.action != StepAction::CancelByLabel
+        {
+            anyhow::bail!(
+                "scenario step {n}: label is only valid for open and cancel_by_label steps"
+            );
+        }
+
+        let order = step.order.as_str();
+        if step.action.uses_order() && !open.contains_key(order) {
+            anyhow::bail!(
+                "scenario step {n}: {} refers to order '{order}', which is not open",
+                step.action.as_str()
+            );
+        }
+        match step.action {
+            action if action.opens_order() => {
+                for k in 0..step.repeat {
+                    let name = step.order_name(k);
+                    if open.contains_key(&name) {
+                        anyhow::bail!(
+                            "scenario step {n}: order '{name}' is still open (use another `order` name)"
+                        );
+                    }
+                    open.insert(name, step.label.as_deref());
+                }
+            }
+            StepAction::Cancel if step.repeat > 1 => {
+                anyhow::bail!("scenario step {n}: order '{order}' can only be cancelled once");
+            }
+            StepAction::Cancel => {
+                open.remove(order);
+            }
+            StepAction::CancelByLabel => {
+                let Some(label) = step.label.as_deref() else {
+                    anyhow::bail!("scenario step {n}: cancel_by_label needs a label");
+                };
+                open.retain(|_, l| *l != Some(label));
+            }
+            action if action.cancels_all() => open.clear(),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Reject mass cancel steps that would also cancel the orders of other chains running at
+/// the same time: each chain only forgets its own orders, so the others' next edit or
+/// cancel would fail.
+///
+/// `concurrent_chains` chains share an instrument and the run's labels; with
+/// `parallel_instruments` the chains of different instruments share the account and labels.
+pub fn validate_step_concurrency(
+    steps: &[ScenarioStep],
+    concurrent_chains: usize,
+    parallel_instruments: bool,
+) -> Result<()> {
+    for (i, step) in steps.iter().enumerate() {
+        let n = i + 1;
+        let action = step.action;
+        if !action.cancels_all() && action != StepAction::CancelByLabel {
+            continue;
+        }
+        if concurrent_chains > 1 {
+            anyhow::bail!(
+                "scenario step {n}: {} would cancel the orders of the other chains (needs concurrent_chains = 1)",
+                action.as_str()
+            );
+        }
+        if parallel_instruments && action != StepAction::CancelAllByInstrument {
+            anyhow::bail!(
+                "scenario step {n}: {} would cancel the orders of the other instruments (use cancel_all_by_instrument or instrument_mode = \"round_robin\")",
+                action.as_str()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn default_order_ref() -> String {
+    "order".to_string()
+}
+
+fn default_repeat() -> usize {
+    1
+}
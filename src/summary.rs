@@ -1,47 +1,439 @@
+use std::collections::BTreeMap;
 use std::fs::File;
 
 use anyhow::{Context, Result};
 use serde::Deserialize;
 
+use crate::config::instrument_currency;
+use crate::latency::{is_connection_event, HEARTBEAT_OP_TYPE};
+
+/// Gap timestamps listed per channel in the feed summary.
+const MAX_LISTED_GAPS: usize = 10;
+
+/// Quantiles (in percent) printed by default.
+pub const DEFAULT_QUANTILES: [f64; 5] = [50.0, 90.0, 99.0, 99.9, 99.99];
+
+/// Buckets per power of two in the log-bucketed histogram (a power of two itself).
+const HISTOGRAM_SUB_BUCKETS: i64 = 8;
+/// Width of the longest histogram bar, in characters.
+const HISTOGRAM_BAR_WIDTH: usize = 40;
+
+/// What the summaries print.
+#[derive(Debug, Clone)]
+pub struct SummaryOptions {
+    /// Quantiles in percent, each in (0, 100].
+    pub quantiles: Vec<f64>,
+    /// Print a log-bucketed histogram of the order RTTs.
+    pub histogram: bool,
+}
+
+/// Check that every quantile lies in (0, 100].
+pub fn validate_quantiles(quantiles: &[f64]) -> Result<()> {
+    if quantiles.is_empty() {
+        anyhow::bail!("at least one summary quantile is needed");
+    }
+    if let Some(p) = quantiles.iter().find(|&&p| !(p > 0.0 && p <= 100.0)) {
+        anyhow::bail!("summary quantiles must be in (0, 100] (got {p})");
+    }
+    Ok(())
+}
+
 /// Minimal view of the CSV rows for summary calculation.
 #[derive(Debug, Deserialize)]
 struct SampleRow {
+    op_type: String,
+    rpc_method: String,
+    instrument_name: String,
+    send_ts_mono_ns: i64,
+    recv_ts_mono_ns: i64,
     rtt_mono_us: i64,
+    serialize_us: Option<i64>,
+    write_us: Option<i64>,
+    est_outbound_us: Option<i64>,
+    est_inbound_us: Option<i64>,
     tick_to_send_us: Option<i64>,
     tick_to_ack_us: Option<i64>,
     ack_delta_prev_us: Option<i64>,
+    engine_us_diff: Option<i64>,
+    #[serde(default)]
+    cancelled_count: Option<i64>,
+    error_code: Option<i64>,
+    #[serde(default)]
+    timed_out: bool,
 }
 
-pub fn print_summary_from_csv(path: &str) -> Result<()> {
+/// How a request ended, for the per-operation breakdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    Ok,
+    Error,
+    TimedOut,
+}
+
+impl Outcome {
+    fn of(row: &SampleRow) -> Self {
+        if row.timed_out {
+            Outcome::TimedOut
+        } else if row.error_code.is_some() {
+            Outcome::Error
+        } else {
+            Outcome::Ok
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Error => "error",
+            Outcome::TimedOut => "timed out",
+        }
+    }
+}
+
+/// Group of the per-operation breakdown: what was sent, where, and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct OperationKey {
+    op_type: String,
+    rpc_method: String,
+    /// Empty for requests without an instrument.
+    instrument_name: String,
+    outcome: Outcome,
+}
+
+impl OperationKey {
+    fn label(&self) -> String {
+        let mut label = format!("{} ({}", self.op_type, self.rpc_method);
+        if !self.instrument_name.is_empty() {
+            label.push_str(", ");
+            label.push_str(&self.instrument_name);
+        }
+        label.push_str(&format!(", {})", self.outcome.as_str()));
+        label
+    }
+}
+
+/// The latency series of one group of requests.
+#[derive(Debug, Default)]
+struct LatencySeries {
+    rtts: Vec<i64>,
+    tick_send: Vec<i64>,
+    tick_ack: Vec<i64>,
+    ack_delta: Vec<i64>,
+    serialize: Vec<i64>,
+    write: Vec<i64>,
+    outbound: Vec<i64>,
+    inbound: Vec<i64>,
+    /// Deribit processing time (`usOut − usIn`).
+    engine: Vec<i64>,
+    /// Everything outside the engine (`rtt_mono_us − engine_us_diff`): network and client.
+    network_client: Vec<i64>,
+}
+
+impl LatencySeries {
+    fn push(&mut self, row: &SampleRow) {
+        self.rtts.push(row.rtt_mono_us);
+        if let Some(v) = row.tick_to_send_us {
+            self.tick_send.push(v);
+        }
+        if let Some(v) = row.tick_to_ack_us {
+            self.tick_ack.push(v);
+        }
+        if let Some(v) = row.ack_delta_prev_us {
+            self.ack_delta.push(v);
+        }
+        if let Some(v) = row.serialize_us {
+            self.serialize.push(v);
+        }
+        if let Some(v) = row.write_us {
+            self.write.push(v);
+        }
+        if let Some(v) = row.est_outbound_us {
+            self.outbound.push(v);
+        }
+        if let Some(v) = row.est_inbound_us {
+            self.inbound.push(v);
+        }
+        if let Some(v) = row.engine_us_diff {
+            self.engine.push(v);
+            self.network_client.push(row.rtt_mono_us - v);
+        }
+    }
+
+    /// Labelled series in the order they are printed.
+    fn labelled(&mut self) -> [(&'static str, &mut Vec<i64>); 10] {
+        [
+            ("RTT (Send → Ack)", &mut self.rtts),
+            ("Engine (usDiff)", &mut self.engine),
+            ("Network + client (RTT − usDiff)", &mut self.network_client),
+            ("Tick → Send", &mut self.tick_send),
+            ("Tick → Ack", &mut self.tick_ack),
+            ("Ack interval (prev Ack → this Ack)", &mut self.ack_delta),
+            ("Client serialize", &mut self.serialize),
+            ("Client write (queue + socket write)", &mut self.write),
+            ("Est. outbound (Send → usIn)", &mut self.outbound),
+            ("Est. inbound (usOut → Ack)", &mut self.inbound),
+        ]
+    }
+}
+
+/// Latency series extracted from one CSV file.
+#[derive(Debug, Default)]
+struct SampleSeries {
+    /// Successful requests (no error, no timeout).
+    ok: LatencySeries,
+    /// Requests answered with an error.
+    errors: usize,
+    /// Requests without a response within the timeout (their RTT is the time waited).
+    timeouts: usize,
+    /// Requests of any outcome.
+    requests: usize,
+    /// First send and last receive of all requests (monotonic ns), for throughput.
+    span_ns: Option<(i64, i64)>,
+    /// RTTs per instrument (successful order requests only).
+    instrument_rtts: BTreeMap<String, Vec<i64>>,
+    /// Every series per operation and outcome.
+    operations: BTreeMap<OperationKey, LatencySeries>,
+    /// Mass cancel RTTs by `op_type` and number of orders cancelled.
+    mass_cancels: BTreeMap<(String, i64), Vec<i64>>,
+    /// Round trips of `public/test` answers to heartbeats.
+    heartbeats: Vec<i64>,
+    disconnects: usize,
+    /// Gap (disconnect → session restored) of every successful reconnect.
+    reconnect_gaps: Vec<i64>,
+}
+
+/// Minimal view of the clock sync CSV rows.
+#[derive(Debug, Deserialize)]
+struct ClockRow {
+    phase: String,
+    delay_us: i64,
+    est_offset_us: Option<i64>,
+    est_uncertainty_us: Option<i64>,
+    drift_ppm: Option<f64>,
+    drift_uncertainty_ppm: Option<f64>,
+}
+
+/// Minimal view of the feed latency CSV rows.
+#[derive(Debug, Deserialize)]
+struct FeedRow {
+    channel: String,
+    recv_ts_mono_ns: i64,
+    recv_ts_wall_iso: String,
+    change_id: Option<i64>,
+    prev_change_id: Option<i64>,
+    #[serde(default)]
+    sequence_gap: bool,
+    feed_latency_us: i64,
+    feed_latency_corrected_us: Option<i64>,
+}
+
+/// Feed latency and book sequence statistics of one channel.
+#[derive(Debug, Default)]
+struct ChannelSeries {
+    latency: Vec<i64>,
+    corrected: Vec<i64>,
+    /// Receive time (wall clock) of every update arriving after a `change_id` gap.
+    gaps: Vec<String>,
+    /// Time from a gap to the next snapshot (resync), in microseconds.
+    resyncs: Vec<i64>,
+    /// Monotonic receive time of a gap not yet followed by a snapshot.
+    open_gap_ns: Option<i64>,
+}
+
+/// Minimal view of the ack → user notification CSV rows.
+#[derive(Debug, Deserialize)]
+struct UserRow {
+    op_type: String,
+    notification: String,
+    ack_to_notification_us: i64,
+    us_out_to_notification_us: Option<i64>,
+}
+
+/// One row of the cancel-on-disconnect CSV (only the columns needed for the summary).
+#[derive(Debug, Deserialize)]
+struct CancelOnDisconnectRow {
+    cancelled: bool,
+    reconnect_us: i64,
+    cleanup_us: Option<i64>,
+    cancel_delay_ms: Option<i64>,
+}
+
+/// Statistics of one latency series, in microseconds.
+#[derive(Debug, Clone)]
+struct Stats {
+    count: usize,
+    mean: f64,
+    stddev: f64,
+    min: i64,
+    max: i64,
+    /// `(quantile in percent, interpolated value)` per configured quantile.
+    quantiles: Vec<(f64, f64)>,
+}
+
+fn load_series(path: &str) -> Result<SampleSeries> {
     let file = File::open(path).with_context(|| format!("failed to open CSV at '{}'", path))?;
     let mut rdr = csv::Reader::from_reader(file);
 
-    let mut rtts = Vec::new();
-    let mut tick_send = Vec::new();
-    let mut tick_ack = Vec::new();
-    let mut ack_delta = Vec::new();
+    let mut series = SampleSeries::default();
 
     for record in rdr.deserialize::<SampleRow>() {
         let row = record?;
-        rtts.push(row.rtt_mono_us);
-        if let Some(v) = row.tick_to_send_us {
-            tick_send.push(v);
+        if is_connection_event(&row.op_type) {
+            match row.op_type.as_str() {
+                "disconnect" => series.disconnects += 1,
+                "reconnect" => series.reconnect_gaps.push(row.rtt_mono_us),
+                _ => {}
+            }
+            continue;
         }
-        if let Some(v) = row.tick_to_ack_us {
-            tick_ack.push(v);
+        if row.op_type == HEARTBEAT_OP_TYPE {
+            series.heartbeats.push(row.rtt_mono_us);
+            continue;
         }
-        if let Some(v) = row.ack_delta_prev_us {
-            ack_delta.push(v);
+        let outcome = Outcome::of(&row);
+        series.requests += 1;
+        match outcome {
+            Outcome::Ok => {}
+            Outcome::Error => series.errors += 1,
+            Outcome::TimedOut => series.timeouts += 1,
+        }
+        series.span_ns = Some(match series.span_ns {
+            Some((first, last)) => (
+                first.min(row.send_ts_mono_ns),
+                last.max(row.recv_ts_mono_ns),
+            ),
+            None => (row.send_ts_mono_ns, row.recv_ts_mono_ns),
+        });
+        series
+            .operations
+            .entry(OperationKey {
+                op_type: row.op_type.clone(),
+                rpc_method: row.rpc_method.clone(),
+                instrument_name: row.instrument_name.clone(),
+                outcome,
+            })
+            .or_default()
+            .push(&row);
+
+        // Failed requests only show up in their own groups
+        if outcome != Outcome::Ok {
+            continue;
+        }
+        series.ok.push(&row);
+        if !row.instrument_name.is_empty() {
+            series
+                .instrument_rtts
+                .entry(row.instrument_name.clone())
+                .or_default()
+                .push(row.rtt_mono_us);
+        }
+        if let Some(n) = row.cancelled_count {
+            series
+                .mass_cancels
+                .entry((row.op_type, n))
+                .or_default()
+                .push(row.rtt_mono_us);
         }
     }
 
+    Ok(series)
+}
+
+pub fn print_summary_from_csv(path: &str, opts: &SummaryOptions) -> Result<()> {
+    let mut series = load_series(path)?;
+
     println!();
     println!("==================== LATENCY SUMMARY ====================");
+    println!();
+    println!("Successful requests (errors and timeouts are listed per operation)");
+
+    for (label, data) in series.ok.labelled() {
+        print_stats(label, data, opts);
+    }
+    if opts.histogram {
+        print_histogram("RTT (Send → Ack)", &series.ok.rtts);
+    }
+
+    // Breakdown only for runs trading several instruments
+    if series.instrument_rtts.len() > 1 {
+        let mut currency_rtts: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+        for (instrument, rtts) in &series.instrument_rtts {
+            currency_rtts
+                .entry(instrument_currency(instrument))
+                .or_default()
+                .extend(rtts);
+        }
+        for (currency, rtts) in &mut currency_rtts {
+            print_stats(&format!("RTT {currency} (per currency)"), rtts, opts);
+        }
+        for (instrument, rtts) in &mut series.instrument_rtts {
+            print_stats(&format!("RTT {instrument}"), rtts, opts);
+        }
+    }
+
+    if !series.operations.is_empty() {
+        println!();
+        println!("Per operation (op_type, rpc_method, instrument, outcome):");
+    }
+    for (key, operation) in &mut series.operations {
+        println!();
+        println!("--- {} ---", key.label());
+        for (label, data) in operation.labelled() {
+            if !data.is_empty() {
+                print_stats(label, data, opts);
+            }
+        }
+    }
+
+    for ((op_type, cancelled), rtts) in &mut series.mass_cancels {
+        print_stats(
+            &format!("RTT {op_type} ({cancelled} order(s) cancelled)"),
+            rtts,
+            opts,
+        );
+    }
 
-    print_stats("RTT (Send → Ack)", &mut rtts);
-    print_stats("Tick → Send", &mut tick_send);
-    print_stats("Tick → Ack", &mut tick_ack);
-    print_stats("Ack interval (prev Ack → this Ack)", &mut ack_delta);
+    if let Some((first, last)) = series.span_ns {
+        let secs = (last - first) as f64 / 1e9;
+        if secs > 0.0 {
+            println!();
+            println!(
+                "Throughput: {} requests in {:.3} s ({:.1} req/s)",
+                series.requests,
+                secs,
+                series.requests as f64 / secs
+            );
+        }
+    }
+
+    if series.errors > 0 {
+        println!();
+        println!(
+            "Errors: {} request(s) answered with an error",
+            series.errors
+        );
+    }
+    if series.timeouts > 0 {
+        println!();
+        println!(
+            "Timeouts: {} request(s) without response (RTT counted up to the timeout)",
+            series.timeouts
+        );
+    }
+
+    if !series.heartbeats.is_empty() {
+        print_stats("Heartbeat RTT (public/test)", &mut series.heartbeats, opts);
+    }
+
+    if series.disconnects > 0 {
+        println!();
+        println!("Connection: {} disconnect(s)", series.disconnects);
+        print_stats(
+            "Reconnect gap (disconnect → session restored)",
+            &mut series.reconnect_gaps,
+            opts,
+        );
+    }
 
     println!();
     println!("=========================================================");
@@ -50,40 +442,553 @@ pub fn print_summary_from_csv(path: &str) -> Result<()> {
     Ok(())
 }
 
-fn print_stats(label: &str, data: &mut [i64]) {
+/// Print offset, drift and probe delay from a clock sync CSV.
+pub fn print_clock_summary_from_csv(path: &str, opts: &SummaryOptions) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to open CSV at '{}'", path))?;
+    let mut rdr = csv::Reader::from_reader(file);
+    let rows = rdr
+        .deserialize::<ClockRow>()
+        .collect::<Result<Vec<_>, _>>()?;
+
     println!();
-    println!("{label}:");
+    println!("================== CLOCK SYNC SUMMARY ===================");
 
-    if data.is_empty() {
+    let pre_run = rows.iter().filter(|r| r.phase == "pre_run").count();
+    println!();
+    println!(
+        "Probes: {} ({} before the run, {} during the run)",
+        rows.len(),
+        pre_run,
+        rows.len() - pre_run
+    );
+
+    let estimates: Vec<(i64, i64)> = rows
+        .iter()
+        .filter_map(|r| Some((r.est_offset_us?, r.est_uncertainty_us?)))
+        .collect();
+    if let Some(&(offset, uncertainty)) = estimates.last() {
+        let min = estimates.iter().map(|e| e.0).min().unwrap_or(offset);
+        let max = estimates.iter().map(|e| e.0).max().unwrap_or(offset);
+        println!("Offset (Deribit − local): {offset} µs ± {uncertainty} µs (final estimate)");
+        println!("Estimate range:           {min} … {max} µs");
+    }
+
+    match rows
+        .iter()
+        .rev()
+        .find_map(|r| Some((r.drift_ppm?, r.drift_uncertainty_ppm?)))
+    {
+        Some((drift, uncertainty)) => {
+            println!("Drift:                    {drift:+.1} ppm ± {uncertainty:.1} ppm")
+        }
+        None => println!("Drift:                    not enough probes during the run"),
+    }
+
+    let mut delays: Vec<i64> = rows.iter().map(|r| r.delay_us).collect();
+    print_stats("Probe delay (RTT − server time)", &mut delays, opts);
+
+    println!();
+    println!("=========================================================");
+    println!();
+
+    Ok(())
+}
+
+/// Print the exchange-to-local latency of every market data channel in a feed CSV.
+pub fn print_feed_summary_from_csv(path: &str, opts: &SummaryOptions) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to open CSV at '{}'", path))?;
+    let mut rdr = csv::Reader::from_reader(file);
+
+    let mut channels: BTreeMap<String, ChannelSeries> = BTreeMap::new();
+    for record in rdr.deserialize::<FeedRow>() {
+        let row = record?;
+        let series = channels.entry(row.channel).or_default();
+        series.latency.push(row.feed_latency_us);
+        if let Some(v) = row.feed_latency_corrected_us {
+            series.corrected.push(v);
+        }
+
+        // A book update without `prev_change_id` is a snapshot
+        let is_snapshot = row.change_id.is_some() && row.prev_change_id.is_none();
+        if is_snapshot {
+            if let Some(gap_ns) = series.open_gap_ns.take() {
+                series.resyncs.push((row.recv_ts_mono_ns - gap_ns) / 1000);
+            }
+        }
+        if row.sequence_gap {
+            series.gaps.push(row.recv_ts_wall_iso);
+            series.open_gap_ns.get_or_insert(row.recv_ts_mono_ns);
+        }
+    }
+
+    println!();
+    println!("================ MARKET DATA FEED SUMMARY ===============");
+    println!();
+    println!("Exchange timestamp → local receive (exchange timestamps have ms resolution)");
+
+    if channels.is_empty() {
+        println!();
         println!("    no data");
-        return;
+    }
+    for (channel, series) in &mut channels {
+        print_stats(
+            &format!("{channel} (local clock)"),
+            &mut series.latency,
+            opts,
+        );
+        if !series.corrected.is_empty() {
+            print_stats(
+                &format!("{channel} (clock offset corrected)"),
+                &mut series.corrected,
+                opts,
+            );
+        }
     }
 
-    data.sort_unstable();
-    let n = data.len();
+    let gaps: usize = channels.values().map(|s| s.gaps.len()).sum();
+    println!();
+    println!("Book sequence gaps: {gaps}");
+    for (channel, series) in &mut channels {
+        if series.gaps.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{channel}: {} gap(s)", series.gaps.len());
+        for ts in series.gaps.iter().take(MAX_LISTED_GAPS) {
+            println!("    at {ts}");
+        }
+        if series.gaps.len() > MAX_LISTED_GAPS {
+            println!("    ... and {} more", series.gaps.len() - MAX_LISTED_GAPS);
+        }
+        if !series.resyncs.is_empty() {
+            print_stats(
+                &format!("{channel} resync (gap → snapshot)"),
+                &mut series.resyncs,
+                opts,
+            );
+        }
+    }
+
+    println!();
+    println!("=========================================================");
+    println!();
+
+    Ok(())
+}
+
+/// Print the delays between order acks and their user channel notifications, per op_type.
+pub fn print_user_summary_from_csv(path: &str, opts: &SummaryOptions) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to open CSV at '{}'", path))?;
+    let mut rdr = csv::Reader::from_reader(file);
+    let rows = rdr
+        .deserialize::<UserRow>()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!();
+    println!("=============== USER NOTIFICATION SUMMARY ===============");
+
+    let before_ack = rows.iter().filter(|r| r.ack_to_notification_us < 0).count();
+    println!();
+    println!(
+        "Paired notifications: {} ({} arrived before the ack)",
+        rows.len(),
+        before_ack
+    );
+
+    // "<op_type> → <notification>" -> (ack → notification, usOut → notification)
+    let mut groups: BTreeMap<String, (Vec<i64>, Vec<i64>)> = BTreeMap::new();
+    for row in rows {
+        let (ack, us_out) = groups
+            .entry(format!("{} → {}", row.op_type, row.notification))
+            .or_default();
+        ack.push(row.ack_to_notification_us);
+        if let Some(v) = row.us_out_to_notification_us {
+            us_out.push(v);
+        }
+    }
+    for (group, (mut ack, mut us_out)) in groups {
+        print_stats(&format!("{group}: Ack → notification"), &mut ack, opts);
+        if !us_out.is_empty() {
+            print_stats(&format!("{group}: usOut → notification"), &mut us_out, opts);
+        }
+    }
+
+    println!();
+    println!("=========================================================");
+    println!();
+
+    Ok(())
+}
+
+/// Print how quickly cancel-on-disconnect removed the orders of dropped connections.
+pub fn print_cancel_on_disconnect_summary_from_csv(
+    path: &str,
+    opts: &SummaryOptions,
+) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("failed to open CSV at '{}'", path))?;
+    let mut rdr = csv::Reader::from_reader(file);
+    let rows = rdr
+        .deserialize::<CancelOnDisconnectRow>()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!();
+    println!("============= CANCEL-ON-DISCONNECT SUMMARY ==============");
+
+    let cancelled = rows.iter().filter(|r| r.cancelled).count();
+    println!();
+    println!(
+        "Orders cancelled on disconnect: {} of {}",
+        cancelled,
+        rows.len()
+    );
+
+    let mut reconnect: Vec<i64> = rows.iter().map(|r| r.reconnect_us).collect();
+    let mut cleanup: Vec<i64> = rows.iter().filter_map(|r| r.cleanup_us).collect();
+    let mut delay: Vec<i64> = rows
+        .iter()
+        .filter_map(|r| r.cancel_delay_ms)
+        .map(|ms| ms * 1000)
+        .collect();
+    print_stats("Connection drop → reconnected", &mut reconnect, opts);
+    print_stats(
+        "Connection drop → order gone (open orders poll)",
+        &mut cleanup,
+        opts,
+    );
+    if !delay.is_empty() {
+        print_stats(
+            "Connection drop → cancelled (Deribit timestamp, ms resolution)",
+            &mut delay,
+            opts,
+        );
+    }
+
+    println!();
+    println!("=========================================================");
+    println!();
+
+    Ok(())
+}
+
+/// Print the statistics of two CSV files next to each other, plus the change from
+/// `baseline` to `candidate`.
+pub fn print_comparison_from_csv(
+    baseline: &str,
+    candidate: &str,
+    opts: &SummaryOptions,
+) -> Result<()> {
+    let mut base = load_series(baseline)?;
+    let mut cand = load_series(candidate)?;
+
+    println!();
+    println!("=================== LATENCY COMPARISON ==================");
+    println!("    A (baseline):  {baseline}");
+    println!("    B (candidate): {candidate}");
+    println!();
+    println!("Successful requests (errors and timeouts are listed per operation)");
+
+    for ((label, a), (_, b)) in base.ok.labelled().into_iter().zip(cand.ok.labelled()) {
+        print_stats_comparison(label, a, b, opts);
+    }
+
+    // Operations of either run, so ones missing in one run show up as "no data"
+    let mut keys: Vec<OperationKey> = base
+        .operations
+        .keys()
+        .chain(cand.operations.keys())
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+    if !keys.is_empty() {
+        println!();
+        println!("Per operation (op_type, rpc_method, instrument, outcome):");
+    }
+    for key in keys {
+        let mut a = base.operations.remove(&key).unwrap_or_default();
+        let mut b = cand.operations.remove(&key).unwrap_or_default();
+        println!();
+        println!("--- {} ---", key.label());
+        for ((label, a), (_, b)) in a.labelled().into_iter().zip(b.labelled()) {
+            if !a.is_empty() || !b.is_empty() {
+                print_stats_comparison(label, a, b, opts);
+            }
+        }
+    }
+
+    if opts.histogram {
+        print_histogram_comparison("RTT (Send → Ack)", &base.ok.rtts, &cand.ok.rtts);
+    }
+
+    println!();
+    println!("=========================================================");
+    println!();
 
-    let min = data[0];
-    let max = data[n - 1];
-    let median = percentile(data, 50.0);
-    let p90 = percentile(data, 90.0);
-    let p99 = percentile(data, 99.0);
+    Ok(())
+}
+
+/// Print the statistics of `a` (baseline) and `b` (candidate) and the change between them.
+fn print_stats_comparison(label: &str, a: &mut [i64], b: &mut [i64], opts: &SummaryOptions) {
+    println!();
+    println!("{label}:");
+
+    let stats_a = compute_stats(a, &opts.quantiles);
+    let stats_b = compute_stats(b, &opts.quantiles);
+    print_stats_lines("A", stats_a.as_ref());
+    print_stats_lines("B", stats_b.as_ref());
+
+    if let (Some(a), Some(b)) = (stats_a, stats_b) {
+        let mut deltas = vec![format!(
+            "mean: {}",
+            format_delta(a.mean.round() as i64, b.mean.round() as i64)
+        )];
+        for (&(p, qa), &(_, qb)) in a.quantiles.iter().zip(&b.quantiles) {
+            deltas.push(format!(
+                "{}: {}",
+                quantile_label(p),
+                format_delta(qa.round() as i64, qb.round() as i64)
+            ));
+        }
+        deltas.push(format!("max: {}", format_delta(a.max, b.max)));
+        println!("    Δ  {}", deltas.join("   "));
+    }
+}
+
+fn print_stats(label: &str, data: &mut [i64], opts: &SummaryOptions) {
+    println!();
+    println!("{label}:");
+
+    match compute_stats(data, &opts.quantiles) {
+        Some(stats) => print_stats_lines("", Some(&stats)),
+        None => println!("    no data"),
+    }
+}
+
+/// Print `stats` as two lines (moments and extremes, then the quantiles), prefixed by `tag`.
+fn print_stats_lines(tag: &str, stats: Option<&Stats>) {
+    let tag = if tag.is_empty() {
+        String::new()
+    } else {
+        format!("{tag}  ")
+    };
+    let Some(s) = stats else {
+        println!("    {tag}no data");
+        return;
+    };
 
     println!(
-        "    count: {:>6}   min: {:>8} µs   median: {:>8} µs   p90: {:>8} µs   p99: {:>8} µs   max: {:>8} µs",
-        n, min, median, p90, p99, max
+        "    {tag}count: {:>6}   mean: {:>8.0} µs   stddev: {:>8.0} µs   min: {:>8} µs   max: {:>8} µs",
+        s.count, s.mean, s.stddev, s.min, s.max
     );
+    let quantiles: Vec<String> = s
+        .quantiles
+        .iter()
+        .map(|&(p, v)| format!("{}: {:>8.0} µs", quantile_label(p), v))
+        .collect();
+    println!("    {}{}", " ".repeat(tag.len()), quantiles.join("   "));
 }
 
-/// Simple percentile helper: p in [0, 100].
-fn percentile(sorted: &[i64], p: f64) -> i64 {
+/// Short name of a quantile: `median` for 50 %, otherwise `p90`, `p99.9`, ...
+fn quantile_label(p: f64) -> String {
+    if p == 50.0 {
+        "median".to_string()
+    } else {
+        format!("p{p}")
+    }
+}
+
+/// Signed change from `a` to `b`, in µs and percent of `a`.
+fn format_delta(a: i64, b: i64) -> String {
+    let diff = b - a;
+    if a == 0 {
+        format!("{diff:+} µs")
+    } else {
+        format!("{diff:+} µs ({:+.1}%)", diff as f64 / a as f64 * 100.0)
+    }
+}
+
+/// Sort `data` in place and compute its moments and the (interpolated) `quantiles`.
+fn compute_stats(data: &mut [i64], quantiles: &[f64]) -> Option<Stats> {
+    if data.is_empty() {
+        return None;
+    }
+
+    data.sort_unstable();
+    let n = data.len();
+    let mean = data.iter().map(|&v| v as f64).sum::<f64>() / n as f64;
+    // Sample standard deviation (0 for a single sample)
+    let variance = if n > 1 {
+        data.iter().map(|&v| (v as f64 - mean).powi(2)).sum::<f64>() / (n - 1) as f64
+    } else {
+        0.0
+    };
+
+    Some(Stats {
+        count: n,
+        mean,
+        stddev: variance.sqrt(),
+        min: data[0],
+        max: data[n - 1],
+        quantiles: quantiles
+            .iter()
+            .map(|&p| (p, percentile(data, p)))
+            .collect(),
+    })
+}
+
+/// Percentile `p` in [0, 100] of `sorted`, linearly interpolated between the two
+/// closest ranks.
+fn percentile(sorted: &[i64], p: f64) -> f64 {
     if sorted.is_empty() {
-        return 0;
+        return 0.0;
+    }
+    let rank = (p / 100.0) * (sorted.len() - 1) as f64;
+    let lo = rank.floor() as usize;
+    let hi = rank.ceil() as usize;
+    let frac = rank - lo as f64;
+    sorted[lo] as f64 + (sorted[hi] - sorted[lo]) as f64 * frac
+}
+
+/// Lower bound of the log bucket holding `v`: values below `HISTOGRAM_SUB_BUCKETS` get a
+/// bucket each, above that every power-of-two range is split into
+/// `HISTOGRAM_SUB_BUCKETS` equally wide buckets (HDR histogram style, so each bucket is at
+/// most 1/`HISTOGRAM_SUB_BUCKETS` of its lower bound wide).
+fn bucket_floor(v: i64) -> i64 {
+    let sub = HISTOGRAM_SUB_BUCKETS;
+    if v < sub {
+        return v;
+    }
+    let magnitude = 63 - v.leading_zeros() as i64;
+    let width = 1_i64 << (magnitude - sub.trailing_zeros() as i64);
+    v / width * width
+}
+
+/// Width of the bucket starting at `floor` (see [`bucket_floor`]).
+fn bucket_width(floor: i64) -> i64 {
+    let sub = HISTOGRAM_SUB_BUCKETS;
+    if floor < sub {
+        return 1;
+    }
+    let magnitude = 63 - floor.leading_zeros() as i64;
+    1_i64 << (magnitude - sub.trailing_zeros() as i64)
+}
+
+/// Count `data` per log bucket (see [`bucket_floor`]).
+fn log_buckets(data: &[i64]) -> BTreeMap<i64, usize> {
+    let mut buckets = BTreeMap::new();
+    for &v in data {
+        *buckets.entry(bucket_floor(v)).or_insert(0) += 1;
+    }
+    buckets
+}
+
+/// Print an ASCII histogram of `data` over its non-empty log buckets, with the
+/// cumulative share of samples up to each bucket.
+fn print_histogram(label: &str, data: &[i64]) {
+    println!();
+    println!("{label} histogram (log buckets):");
+
+    let buckets = log_buckets(data);
+    let Some(&peak) = buckets.values().max() else {
+        println!("    no data");
+        return;
+    };
+
+    let mut cumulative = 0;
+    for (&floor, &count) in &buckets {
+        cumulative += count;
+        let bar = (count * HISTOGRAM_BAR_WIDTH).div_ceil(peak);
+        println!(
+            "    [{:>8}, {:>8}) µs {:>7}  {:>7.3}%  {}",
+            floor,
+            floor + bucket_width(floor),
+            count,
+            cumulative as f64 / data.len() as f64 * 100.0,
+            "#".repeat(bar)
+        );
+    }
+}
+
+/// Print the log bucket counts of `a` (baseline) and `b` (candidate) next to each other.
+fn print_histogram_comparison(label: &str, a: &[i64], b: &[i64]) {
+    println!();
+    println!("{label} histogram (log buckets, count and cumulative share):");
+
+    let buckets_a = log_buckets(a);
+    let buckets_b = log_buckets(b);
+    let mut floors: Vec<i64> = buckets_a.keys().chain(buckets_b.keys()).copied().collect();
+    floors.sort_unstable();
+    floors.dedup();
+    if floors.is_empty() {
+        println!("    no data");
+        return;
     }
-    if sorted.len() == 1 {
-        return sorted[0];
+
+    let (mut cum_a, mut cum_b) = (0, 0);
+    let share = |cum: usize, n: usize| {
+        if n == 0 {
+            0.0
+        } else {
+            cum as f64 / n as f64 * 100.0
+        }
+    };
+    for floor in floors {
+        let count_a = buckets_a.get(&floor).copied().unwrap_or(0);
+        let count_b = buckets_b.get(&floor).copied().unwrap_or(0);
+        cum_a += count_a;
+        cum_b += count_b;
+        println!(
+            "    [{:>8}, {:>8}) µs   A {:>7} {:>7.3}%   B {:>7} {:>7.3}%",
+            floor,
+            floor + bucket_width(floor),
+            count_a,
+            share(cum_a, a.len()),
+            count_b,
+            share(cum_b, b.len())
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let data: Vec<i64> = (1..=100).collect();
+        assert_eq!(percentile(&data, 0.0), 1.0);
+        assert_eq!(percentile(&data, 50.0), 50.5);
+        assert!((percentile(&data, 99.0) - 99.01).abs() < 1e-9);
+        assert_eq!(percentile(&data, 100.0), 100.0);
+        assert_eq!(percentile(&[42], 99.0), 42.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+
+    #[test]
+    fn bucket_floor_boundaries() {
+        // One bucket per value below HISTOGRAM_SUB_BUCKETS and through the first power of two
+        for v in 0..16 {
+            assert_eq!(bucket_floor(v), v);
+            assert_eq!(bucket_width(v), 1);
+        }
+        assert_eq!(bucket_floor(17), 16);
+        assert_eq!(bucket_floor(31), 30);
+        assert_eq!(bucket_floor(32), 32);
+        assert_eq!(bucket_floor(35), 32);
+        assert_eq!(bucket_floor(1023), 960);
+        assert_eq!(bucket_floor(1024), 1024);
+        assert_eq!(bucket_width(960), 64);
+        assert_eq!(bucket_width(1024), 128);
+    }
+
+    #[test]
+    fn bucket_floor_contains_value() {
+        for v in 0..10_000 {
+            let floor = bucket_floor(v);
+            assert!(
+                floor <= v && v < floor + bucket_width(floor),
+                "{v} not in bucket {floor}"
+            );
+        }
     }
-    let n = sorted.len();
-    let rank = (p / 100.0) * ((n - 1) as f64);
-    let idx = rank.floor() as usize;
-    sorted[idx]
 }
@@ -0,0 +1,69 @@
+use tokio::sync::watch;
+
+use crate::config::TickTriggerKind;
+use crate::order_book::OrderBook;
+
+/// Latest firing tick, shared with the order chains (`None` until the first one).
+pub type TriggerReceiver = watch::Receiver<Option<TriggerTick>>;
+
+/// Raw book tick that fired an order in tick-to-trade mode.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerTick {
+    /// Local receive time of the tick (ns since program start).
+    pub recv_ts_mono_ns: i64,
+}
+
+/// Decides which raw book ticks fire the next open / edit.
+#[derive(Debug)]
+pub struct TickTrigger {
+    kind: TickTriggerKind,
+    every_n: usize,
+    min_price_move: f64,
+    ticks_seen: usize,
+    /// Best bid / ask after the previous tick.
+    last_top: Option<(Option<f64>, Option<f64>)>,
+    /// Mid price at the last fired tick (or the first tick).
+    reference_mid: Option<f64>,
+}
+
+impl TickTrigger {
+    pub fn new(kind: TickTriggerKind, every_n: usize, min_price_move: f64) -> Self {
+        Self {
+            kind,
+            every_n: every_n.max(1),
+            min_price_move,
+            ticks_seen: 0,
+            last_top: None,
+            reference_mid: None,
+        }
+    }
+
+    /// Feed the book after applying a tick; returns whether this tick fires.
+    pub fn on_tick(&mut self, book: &OrderBook) -> bool {
+        self.ticks_seen += 1;
+        match self.kind {
+            TickTriggerKind::EveryNth => self.ticks_seen.is_multiple_of(self.every_n),
+            TickTriggerKind::TopOfBook => {
+                let top = (book.best_bid(), book.best_ask());
+                let changed = self.last_top.is_some_and(|last| last != top);
+                self.last_top = Some(top);
+                changed
+            }
+            TickTriggerKind::PriceMove => {
+                let Some(mid) = book.mid() else {
+                    return false;
+                };
+                let Some(reference) = self.reference_mid else {
+                    self.reference_mid = Some(mid);
+                    return false;
+                };
+                let moved = (mid - reference).abs();
+                let fires = moved > 0.0 && moved >= self.min_price_move;
+                if fires {
+                    self.reference_mid = Some(mid);
+                }
+                fires
+            }
+        }
+    }
+}
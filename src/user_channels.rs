@@ -0,0 +1,358 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::time::Instant;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use csv::Writer;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::clock_sync::OffsetEstimate;
+use crate::latency::create_csv_writer;
+
+/// How long unpaired acks and notifications (and idle orders) are kept, in nanoseconds.
+/// Notifications for orders this run never acknowledged (other processes, mass cancels)
+/// expire after it.
+const UNPAIRED_TTL_NS: i64 = 30_000_000_000;
+/// Minimum time between two sweeps for expired entries, in nanoseconds.
+const PRUNE_INTERVAL_NS: i64 = 1_000_000_000;
+
+/// Private channels subscribed for `instrument` with `subscribe_user_channels`.
+pub fn user_channels(instrument: &str) -> [String; 2] {
+    [
+        format!("user.orders.{instrument}.raw"),
+        format!("user.trades.{instrument}.raw"),
+    ]
+}
+
+/// Order state change from a `user.orders.*` notification.
+#[derive(Debug, Clone)]
+pub struct OrderUpdate {
+    pub order_id: String,
+    pub order_state: String,
+    /// `last_update_timestamp` of the order (milliseconds).
+    pub timestamp_ms: Option<i64>,
+}
+
+/// Own trade from a `user.trades.*` notification.
+#[derive(Debug, Clone)]
+pub struct TradeUpdate {
+    pub order_id: String,
+    pub timestamp_ms: Option<i64>,
+}
+
+/// Parsed `data` of a private user channel notification.
+#[derive(Debug, Clone)]
+pub enum UserUpdate {
+    Orders(Vec<OrderUpdate>),
+    Trades(Vec<TradeUpdate>),
+}
+
+impl UserUpdate {
+    /// Parse the `data` of a `user.orders.*` / `user.trades.*` notification (one object or a list).
+    pub fn from_data(channel: &str, data: &Value) -> Option<Self> {
+        let items: Vec<&Value> = match data.as_array() {
+            Some(items) => items.iter().collect(),
+            None => vec![data],
+        };
+        let order_id = |item: &Value| Some(item.get("order_id")?.as_str()?.to_string());
+
+        if channel.starts_with("user.orders.") {
+            Some(Self::Orders(
+                items
+                    .into_iter()
+                    .filter_map(|item| {
+                        Some(OrderUpdate {
+                            order_id: order_id(item)?,
+                            order_state: item.get("order_state")?.as_str()?.to_string(),
+                            timestamp_ms: item
+                                .get("last_update_timestamp")
+                                .and_then(|v| v.as_i64()),
+                        })
+                    })
+                    .collect(),
+            ))
+        } else if channel.starts_with("user.trades.") {
+            Some(Self::Trades(
+                items
+                    .into_iter()
+                    .filter_map(|item| {
+                        Some(TradeUpdate {
+                            order_id: order_id(item)?,
+                            timestamp_ms: item.get("timestamp").and_then(|v| v.as_i64()),
+                        })
+                    })
+                    .collect(),
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+/// One RPC ack paired with the user channel notification it caused.
+#[derive(Debug, Serialize)]
+pub struct UserNotificationSample {
+    pub order_id: String,
+    /// `op_type` of the acknowledged request (`buy`, `sell`, `edit`, `cancel`).
+    pub op_type: String,
+    pub channel: String,
+    /// `order_state` of an order notification, or `trade`.
+    pub notification: String,
+
+    pub ack_recv_ts_mono_ns: i64,
+    pub notification_recv_ts_mono_ns: i64,
+    pub notification_recv_ts_wall_iso: String,
+    /// Exchange timestamp of the notification (`last_update_timestamp` / trade `timestamp`), ms.
+    pub notification_exchange_ts_ms: Option<i64>,
+
+    /// Notification receive minus ack receive (monotonic); negative if the notification came first.
+    pub ack_to_notification_us: i64,
+
+    /// Engine `usOut` of the ack and the time from it to the notification receive,
+    /// on Deribit's clock (needs the clock offset estimate).
+    pub engine_us_out: Option<i64>,
+    pub us_out_to_notification_us: Option<i64>,
+    pub clock_offset_us: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct Ack {
+    op_type: String,
+    recv_ns: i64,
+    us_out: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct Notification {
+    channel: String,
+    kind: String,
+    recv_ns: i64,
+    recv_wall: DateTime<Utc>,
+    exchange_ts_ms: Option<i64>,
+}
+
+/// Acks and notifications of one order not yet paired.
+#[derive(Debug, Default)]
+struct OrderTimeline {
+    acks: VecDeque<Ack>,
+    states: VecDeque<Notification>,
+    /// Ack of the request that opened the order; trades are paired with it.
+    open_ack: Option<Ack>,
+    trades: Vec<Notification>,
+    /// Last ack or notification of the order.
+    last_seen_ns: i64,
+}
+
+impl OrderTimeline {
+    /// Drop unpaired entries received before `cutoff_ns`; `true` if nothing is left and
+    /// the order was idle since then.
+    fn expire(&mut self, cutoff_ns: i64) -> bool {
+        self.acks.retain(|a| a.recv_ns >= cutoff_ns);
+        self.states.retain(|n| n.recv_ns >= cutoff_ns);
+        self.trades.retain(|n| n.recv_ns >= cutoff_ns);
+        self.acks.is_empty()
+            && self.states.is_empty()
+            && self.trades.is_empty()
+            && self.last_seen_ns < cutoff_ns
+    }
+}
+
+/// Pairs RPC acks with `user.orders` / `user.trades` notifications per order_id and writes
+/// the delays to their own CSV.
+///
+/// Every acknowledged request changes the order once, so the n-th ack of an order is paired
+/// with its n-th `user.orders` notification, whichever arrives first. Trades are paired with
+/// the ack of the request that opened the order.
+///
+/// A request on an order that timed out or failed leaves it unknown whether a notification
+/// follows, so the order is no longer paired. Unpaired entries expire after
+/// `UNPAIRED_TTL_NS`.
+pub struct UserNotificationTracker {
+    writer: Writer<File>,
+    program_start: Instant,
+    orders: HashMap<String, OrderTimeline>,
+    /// Orders no longer paired, with the time of the failed request.
+    desynced: HashMap<String, i64>,
+    last_prune_ns: i64,
+}
+
+impl UserNotificationTracker {
+    pub fn new(csv_path: &str, program_start: Instant) -> Result<Self> {
+        Ok(Self {
+            writer: create_csv_writer(csv_path)?,
+            program_start,
+            orders: HashMap::new(),
+            desynced: HashMap::new(),
+            last_prune_ns: 0,
+        })
+    }
+
+    /// Stop pairing `order_id` after a request on it timed out or failed at `ts_mono`.
+    pub fn record_failed_request(&mut self, order_id: &str, ts_mono: Instant) {
+        let ts_ns = self.ns_since_start(ts_mono);
+        self.orders.remove(order_id);
+        self.desynced.insert(order_id.to_string(), ts_ns);
+    }
+
+    /// Record the ack of a request that changed `order_id`.
+    pub fn record_ack(
+        &mut self,
+        order_id: &str,
+        op_type: &str,
+        recv_ts_mono: Instant,
+        us_out: Option<i64>,
+        clock: Option<OffsetEstimate>,
+    ) -> Result<()> {
+        let ack = Ack {
+            op_type: op_type.to_string(),
+            recv_ns: self.ns_since_start(recv_ts_mono),
+            us_out,
+        };
+        self.prune(ack.recv_ns);
+        if self.desynced.contains_key(order_id) {
+            return Ok(());
+        }
+        let timeline = self.orders.entry(order_id.to_string()).or_default();
+        timeline.last_seen_ns = ack.recv_ns;
+
+        let mut pairs = Vec::new();
+        if timeline.open_ack.is_none() {
+            timeline.open_ack = Some(ack.clone());
+            pairs.extend(timeline.trades.drain(..).map(|n| (ack.clone(), n)));
+        }
+        match timeline.states.pop_front() {
+            Some(notification) => pairs.push((ack, notification)),
+            None => timeline.acks.push_back(ack),
+        }
+
+        for (ack, notification) in pairs {
+            self.write(order_id, &ack, &notification, clock)?;
+        }
+        Ok(())
+    }
+
+    /// Record a user channel notification received at `recv_ts_mono` / `recv_ts_wall`.
+    pub fn record_notification(
+        &mut self,
+        channel: &str,
+        update: &UserUpdate,
+        recv_ts_mono: Instant,
+        recv_ts_wall: DateTime<Utc>,
+        clock: Option<OffsetEstimate>,
+    ) -> Result<()> {
+        let recv_ns = self.ns_since_start(recv_ts_mono);
+        self.prune(recv_ns);
+        let notification = |kind: &str, exchange_ts_ms| Notification {
+            channel: channel.to_string(),
+            kind: kind.to_string(),
+            recv_ns,
+            recv_wall: recv_ts_wall,
+            exchange_ts_ms,
+        };
+
+        let mut pairs = Vec::new();
+        match update {
+            UserUpdate::Orders(orders) => {
+                for order in orders {
+                    if self.desynced.contains_key(&order.order_id) {
+                        if is_closed(&order.order_state) {
+                            self.desynced.remove(&order.order_id);
+                        }
+                        continue;
+                    }
+                    let n = notification(&order.order_state, order.timestamp_ms);
+                    let timeline = self.orders.entry(order.order_id.clone()).or_default();
+                    timeline.last_seen_ns = recv_ns;
+                    match timeline.acks.pop_front() {
+                        Some(ack) => pairs.push((order.order_id.clone(), ack, n)),
+                        None => timeline.states.push_back(n),
+                    }
+                }
+            }
+            UserUpdate::Trades(trades) => {
+                for trade in trades {
+                    if self.desynced.contains_key(&trade.order_id) {
+                        continue;
+                    }
+                    let n = notification("trade", trade.timestamp_ms);
+                    let timeline = self.orders.entry(trade.order_id.clone()).or_default();
+                    timeline.last_seen_ns = recv_ns;
+                    match timeline.open_ack.clone() {
+                        Some(ack) => pairs.push((trade.order_id.clone(), ack, n)),
+                        None => timeline.trades.push(n),
+                    }
+                }
+            }
+        }
+
+        for (order_id, ack, notification) in pairs {
+            self.write(&order_id, &ack, &notification, clock)?;
+        }
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        order_id: &str,
+        ack: &Ack,
+        notification: &Notification,
+        clock: Option<OffsetEstimate>,
+    ) -> Result<()> {
+        let us_out_to_notification_us = match (ack.us_out, clock) {
+            (Some(us_out), Some(est)) => {
+                Some(notification.recv_wall.timestamp_micros() + est.offset_us - us_out)
+            }
+            _ => None,
+        };
+        let sample = UserNotificationSample {
+            order_id: order_id.to_string(),
+            op_type: ack.op_type.clone(),
+            channel: notification.channel.clone(),
+            notification: notification.kind.clone(),
+            ack_recv_ts_mono_ns: ack.recv_ns,
+            notification_recv_ts_mono_ns: notification.recv_ns,
+            notification_recv_ts_wall_iso: notification.recv_wall.to_rfc3339(),
+            notification_exchange_ts_ms: notification.exchange_ts_ms,
+            ack_to_notification_us: ((notification.recv_ns - ack.recv_ns) as f64 / 1000.0).round()
+                as i64,
+            engine_us_out: ack.us_out,
+            us_out_to_notification_us,
+            clock_offset_us: clock.map(|est| est.offset_us),
+        };
+        self.writer.serialize(sample)?;
+        self.writer.flush()?;
+
+        // Orders without anything left to pair are done once closed
+        if is_closed(&notification.kind) {
+            if let Some(timeline) = self.orders.get(order_id) {
+                if timeline.acks.is_empty() && timeline.states.is_empty() {
+                    self.orders.remove(order_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop entries older than `UNPAIRED_TTL_NS` (at most once per `PRUNE_INTERVAL_NS`).
+    fn prune(&mut self, now_ns: i64) {
+        if now_ns - self.last_prune_ns < PRUNE_INTERVAL_NS {
+            return;
+        }
+        self.last_prune_ns = now_ns;
+        let cutoff_ns = now_ns - UNPAIRED_TTL_NS;
+        self.orders
+            .retain(|_, timeline| !timeline.expire(cutoff_ns));
+        self.desynced.retain(|_, ts_ns| *ts_ns >= cutoff_ns);
+    }
+
+    fn ns_since_start(&self, t: Instant) -> i64 {
+        t.duration_since(self.program_start).as_nanos() as i64
+    }
+}
+
+/// Final `order_state` of an order.
+fn is_closed(order_state: &str) -> bool {
+    matches!(order_state, "cancelled" | "filled" | "rejected")
+}